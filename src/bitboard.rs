@@ -0,0 +1,469 @@
+use crate::board::{Color, Kind, Location};
+use std::ops::{BitAnd, BitAndAssign, BitOr, BitOrAssign, BitXor, BitXorAssign, Not};
+use std::sync::OnceLock;
+
+/// A set of squares, one bit per square with a1 as bit 0, b1 as bit 1 and h8 as bit 63.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Hash)]
+pub struct Bitboard(pub u64);
+
+pub fn square_index(location: &Location) -> usize {
+    return (location.rank * 8 + location.file) as usize;
+}
+
+pub fn location_from_index(index: usize) -> Location {
+    return Location {
+        rank: (index / 8) as u8,
+        file: (index % 8) as u8,
+    };
+}
+
+impl Bitboard {
+    pub const EMPTY: Bitboard = Bitboard(0);
+
+    pub fn from_location(location: &Location) -> Self {
+        return Bitboard(1 << square_index(location));
+    }
+
+    pub fn contains(&self, location: &Location) -> bool {
+        return self.0 & (1 << square_index(location)) != 0;
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.0 == 0;
+    }
+
+    pub fn count(&self) -> u32 {
+        return self.0.count_ones();
+    }
+
+    /// The lowest square in the set, if any.
+    pub fn first(&self) -> Option<Location> {
+        if self.0 == 0 {
+            return None;
+        }
+        return Some(location_from_index(self.0.trailing_zeros() as usize));
+    }
+
+    pub fn squares(&self) -> Squares {
+        return Squares(self.0);
+    }
+}
+
+/// Iterates over the squares of a bitboard from a1 to h8.
+pub struct Squares(u64);
+
+impl Iterator for Squares {
+    type Item = Location;
+
+    fn next(&mut self) -> Option<Location> {
+        if self.0 == 0 {
+            return None;
+        }
+        let index = self.0.trailing_zeros() as usize;
+        self.0 &= self.0 - 1;
+        return Some(location_from_index(index));
+    }
+}
+
+impl BitAnd for Bitboard {
+    type Output = Bitboard;
+
+    fn bitand(self, other: Bitboard) -> Bitboard {
+        return Bitboard(self.0 & other.0);
+    }
+}
+
+impl BitOr for Bitboard {
+    type Output = Bitboard;
+
+    fn bitor(self, other: Bitboard) -> Bitboard {
+        return Bitboard(self.0 | other.0);
+    }
+}
+
+impl BitXor for Bitboard {
+    type Output = Bitboard;
+
+    fn bitxor(self, other: Bitboard) -> Bitboard {
+        return Bitboard(self.0 ^ other.0);
+    }
+}
+
+impl Not for Bitboard {
+    type Output = Bitboard;
+
+    fn not(self) -> Bitboard {
+        return Bitboard(!self.0);
+    }
+}
+
+impl BitAndAssign for Bitboard {
+    fn bitand_assign(&mut self, other: Bitboard) {
+        self.0 &= other.0;
+    }
+}
+
+impl BitOrAssign for Bitboard {
+    fn bitor_assign(&mut self, other: Bitboard) {
+        self.0 |= other.0;
+    }
+}
+
+impl BitXorAssign for Bitboard {
+    fn bitxor_assign(&mut self, other: Bitboard) {
+        self.0 ^= other.0;
+    }
+}
+
+const KNIGHT_OFFSETS: [(i8, i8); 8] = [
+    (1, 2),
+    (2, 1),
+    (2, -1),
+    (1, -2),
+    (-1, -2),
+    (-2, -1),
+    (-2, 1),
+    (-1, 2),
+];
+
+const KING_OFFSETS: [(i8, i8); 8] = [
+    (1, 0),
+    (1, 1),
+    (0, 1),
+    (-1, 1),
+    (-1, 0),
+    (-1, -1),
+    (0, -1),
+    (1, -1),
+];
+
+const BISHOP_DIRECTIONS: [(i8, i8); 4] = [(1, 1), (1, -1), (-1, 1), (-1, -1)];
+
+const ROOK_DIRECTIONS: [(i8, i8); 4] = [(0, 1), (1, 0), (0, -1), (-1, 0)];
+
+/// Attack sets for a piece that jumps by fixed (rank, file) offsets, indexed by square.
+const fn leaper_attacks(offsets: &[(i8, i8)]) -> [u64; 64] {
+    let mut result = [0; 64];
+    let mut square = 0;
+    while square < 64 {
+        let rank = (square / 8) as i8;
+        let file = (square % 8) as i8;
+        let mut i = 0;
+        while i < offsets.len() {
+            let target_rank = rank + offsets[i].0;
+            let target_file = file + offsets[i].1;
+            if target_rank >= 0 && target_rank < 8 && target_file >= 0 && target_file < 8 {
+                result[square] |= 1 << (target_rank * 8 + target_file);
+            }
+            i += 1;
+        }
+        square += 1;
+    }
+    return result;
+}
+
+const KNIGHT_ATTACKS: [u64; 64] = leaper_attacks(&KNIGHT_OFFSETS);
+const KING_ATTACKS: [u64; 64] = leaper_attacks(&KING_OFFSETS);
+const PAWN_ATTACKS: [[u64; 64]; 2] = [
+    leaper_attacks(&[(1, -1), (1, 1)]),
+    leaper_attacks(&[(-1, -1), (-1, 1)]),
+];
+
+pub fn knight_attacks(location: &Location) -> Bitboard {
+    return Bitboard(KNIGHT_ATTACKS[square_index(location)]);
+}
+
+pub fn king_attacks(location: &Location) -> Bitboard {
+    return Bitboard(KING_ATTACKS[square_index(location)]);
+}
+
+/// The squares a pawn of `color` on `location` attacks.
+pub fn pawn_attacks(color: Color, location: &Location) -> Bitboard {
+    return Bitboard(PAWN_ATTACKS[color.index()][square_index(location)]);
+}
+
+/// Squares reachable along `directions` from `location`, stopping at and including the first
+/// occupied square in each direction. Only used to fill the magic tables.
+fn ray_attacks(location: &Location, occupied: Bitboard, directions: &[(i8, i8)]) -> Bitboard {
+    let mut result = Bitboard::EMPTY;
+    for direction in directions.iter() {
+        let mut target = *location;
+        while let Some(next) = target.move_relative(Color::White, direction.0, direction.1) {
+            let next_bit = Bitboard::from_location(&next);
+            result |= next_bit;
+            if !(occupied & next_bit).is_empty() {
+                break;
+            }
+            target = next;
+        }
+    }
+    return result;
+}
+
+const RANK_1: u64 = 0xff;
+const RANK_8: u64 = 0xff << 56;
+const FILE_A: u64 = 0x0101_0101_0101_0101;
+const FILE_H: u64 = FILE_A << 7;
+
+/// The lookup data for one slider on one square: the squares whose occupancy matters, and the
+/// multiplier and shift that hash them into a slot of the shared attack table.
+#[derive(Copy, Clone, Default)]
+struct Magic {
+    mask: u64,
+    magic: u64,
+    shift: u32,
+    offset: usize,
+}
+
+impl Magic {
+    fn index(&self, occupied: Bitboard) -> usize {
+        let hash = (occupied.0 & self.mask).wrapping_mul(self.magic) >> self.shift;
+        return self.offset + hash as usize;
+    }
+}
+
+struct SliderTables {
+    bishops: [Magic; 64],
+    rooks: [Magic; 64],
+    attacks: Vec<u64>,
+}
+
+static SLIDER_TABLES: OnceLock<SliderTables> = OnceLock::new();
+
+fn slider_tables() -> &'static SliderTables {
+    return SLIDER_TABLES.get_or_init(|| {
+        let mut attacks: Vec<u64> = Vec::with_capacity(5248 + 102_400);
+        let mut bishops = [Magic::default(); 64];
+        let mut rooks = [Magic::default(); 64];
+        for square in 0..64 {
+            bishops[square] = find_magic(square, &BISHOP_DIRECTIONS, &mut attacks);
+            rooks[square] = find_magic(square, &ROOK_DIRECTIONS, &mut attacks);
+        }
+        return SliderTables {
+            bishops: bishops,
+            rooks: rooks,
+            attacks: attacks,
+        };
+    });
+}
+
+/// Seeds for the magic search on each rank, chosen by trying the first few thousand and keeping
+/// the quickest, so building the tables stays fast even in debug builds.
+const MAGIC_SEEDS: [u64; 8] = [1218, 537, 164, 1591, 1070, 192, 785, 1400];
+
+/// splitmix64. Its seeds are fixed, so the same magics are found on every run.
+struct SplitMix(u64);
+
+impl SplitMix {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut result = self.0;
+        result = (result ^ (result >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        result = (result ^ (result >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        return result ^ (result >> 31);
+    }
+
+    /// Good magics tend to have few bits set. Taking them from ANDed draws, and skipping those
+    /// that leave the top byte of the product sparse, follows Tord Romstad's public domain
+    /// magic finder.
+    fn sparse(&mut self) -> u64 {
+        return self.next() & self.next() & self.next();
+    }
+}
+
+/// Searches for a multiplier that maps every occupancy of the relevant squares to a slot holding
+/// the right attack set, and appends those slots to `attacks`.
+fn find_magic(square: usize, directions: &[(i8, i8)], attacks: &mut Vec<u64>) -> Magic {
+    let location = location_from_index(square);
+    let rank = RANK_1 << (8 * location.rank);
+    let file = FILE_A << location.file;
+    // Pieces on the last square of a ray never change what is attacked.
+    let edges = ((RANK_1 | RANK_8) & !rank) | ((FILE_A | FILE_H) & !file);
+    let mask = ray_attacks(&location, Bitboard::EMPTY, directions).0 & !edges;
+    let bits = mask.count_ones();
+    let size = 1 << bits;
+
+    let mut occupancies: Vec<u64> = Vec::with_capacity(size);
+    let mut reference: Vec<u64> = Vec::with_capacity(size);
+    let mut subset: u64 = 0;
+    loop {
+        occupancies.push(subset);
+        reference.push(ray_attacks(&location, Bitboard(subset), directions).0);
+        subset = subset.wrapping_sub(mask) & mask;
+        if subset == 0 {
+            break;
+        }
+    }
+
+    let shift = 64 - bits;
+    let mut random = SplitMix(MAGIC_SEEDS[location.rank as usize]);
+    let mut table: Vec<u64> = vec![0; size];
+    let mut filled: Vec<u32> = vec![0; size];
+    let mut attempt: u32 = 0;
+    loop {
+        let magic = random.sparse();
+        if (mask.wrapping_mul(magic) >> 56).count_ones() < 6 {
+            continue;
+        }
+        attempt += 1;
+        let mut found = true;
+        for (occupied, attack) in occupancies.iter().zip(reference.iter()) {
+            let index = (occupied.wrapping_mul(magic) >> shift) as usize;
+            if filled[index] != attempt {
+                filled[index] = attempt;
+                table[index] = *attack;
+            } else if table[index] != *attack {
+                found = false;
+                break;
+            }
+        }
+        if found {
+            let offset = attacks.len();
+            attacks.extend_from_slice(&table);
+            return Magic {
+                mask: mask,
+                magic: magic,
+                shift: shift,
+                offset: offset,
+            };
+        }
+    }
+}
+
+/// Bishop attacks from `location` with the given squares occupied. The lookup tables are built
+/// the first time any slider attacks are asked for.
+pub fn bishop_attacks(location: &Location, occupied: Bitboard) -> Bitboard {
+    let tables = slider_tables();
+    let magic = &tables.bishops[square_index(location)];
+    return Bitboard(tables.attacks[magic.index(occupied)]);
+}
+
+pub fn rook_attacks(location: &Location, occupied: Bitboard) -> Bitboard {
+    let tables = slider_tables();
+    let magic = &tables.rooks[square_index(location)];
+    return Bitboard(tables.attacks[magic.index(occupied)]);
+}
+
+pub fn queen_attacks(location: &Location, occupied: Bitboard) -> Bitboard {
+    return bishop_attacks(location, occupied) | rook_attacks(location, occupied);
+}
+
+/// Where every piece is, as one bitboard per color and kind.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Bitboards {
+    pieces: [[Bitboard; 6]; 2],
+    colors: [Bitboard; 2],
+}
+
+impl Bitboards {
+    pub fn new() -> Self {
+        return Self::default();
+    }
+
+    /// Adds or removes a piece; the same call undoes itself.
+    pub fn toggle(&mut self, color: Color, kind: Kind, location: &Location) {
+        let bit = Bitboard::from_location(location);
+        self.pieces[color.index()][kind.index()] ^= bit;
+        self.colors[color.index()] ^= bit;
+    }
+
+    pub fn pieces(&self, color: Color, kind: Kind) -> Bitboard {
+        return self.pieces[color.index()][kind.index()];
+    }
+
+    pub fn color(&self, color: Color) -> Bitboard {
+        return self.colors[color.index()];
+    }
+
+    pub fn occupied(&self) -> Bitboard {
+        return self.colors[0] | self.colors[1];
+    }
+
+    /// The pieces of `by` attacking `location`, with sliders seeing through anything not in
+    /// `occupied`. Pieces missing from `occupied` are left out as if already captured.
+    pub fn attackers(&self, location: &Location, by: Color, occupied: Bitboard) -> Bitboard {
+        let queens = self.pieces(by, Kind::Queen);
+        // A pawn of `by` attacks `location` exactly when a pawn of the other color on
+        // `location` would attack it back
+        let attackers = (pawn_attacks(by.opponent(), location) & self.pieces(by, Kind::Pawn))
+            | (knight_attacks(location) & self.pieces(by, Kind::Knight))
+            | (king_attacks(location) & self.pieces(by, Kind::King))
+            | (bishop_attacks(location, occupied) & (self.pieces(by, Kind::Bishop) | queens))
+            | (rook_attacks(location, occupied) & (self.pieces(by, Kind::Rook) | queens));
+        return attackers & occupied;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn squares(bitboard: Bitboard) -> Vec<String> {
+        return bitboard.squares().map(|location| location.pgn()).collect();
+    }
+
+    fn location(pgn: &str) -> Location {
+        return Location::parse_pgn(pgn).unwrap();
+    }
+
+    #[test]
+    fn leaper_attacks_stay_on_the_board() {
+        assert_eq!(squares(knight_attacks(&location("a1"))), vec!["c2", "b3"]);
+        assert_eq!(knight_attacks(&location("e4")).count(), 8);
+        assert_eq!(
+            squares(king_attacks(&location("h8"))),
+            vec!["g7", "h7", "g8"]
+        );
+        assert_eq!(
+            squares(pawn_attacks(Color::White, &location("a2"))),
+            vec!["b3"]
+        );
+        assert_eq!(
+            squares(pawn_attacks(Color::Black, &location("e7"))),
+            vec!["d6", "f6"]
+        );
+    }
+
+    #[test]
+    fn slider_attacks_stop_at_blockers() {
+        let occupied =
+            Bitboard::from_location(&location("d6")) | Bitboard::from_location(&location("f4"));
+        assert_eq!(
+            squares(rook_attacks(&location("d4"), occupied)),
+            vec!["d1", "d2", "d3", "a4", "b4", "c4", "e4", "f4", "d5", "d6"]
+        );
+        assert_eq!(bishop_attacks(&location("a1"), Bitboard::EMPTY).count(), 7);
+        assert_eq!(queen_attacks(&location("d4"), Bitboard::EMPTY).count(), 27);
+    }
+
+    #[test]
+    fn magic_lookups_match_ray_walks() {
+        let mut random = SplitMix(1);
+        for square in 0..64 {
+            let from = location_from_index(square);
+            for _ in 0..32 {
+                let occupied = Bitboard(random.sparse());
+                assert_eq!(
+                    bishop_attacks(&from, occupied),
+                    ray_attacks(&from, occupied, &BISHOP_DIRECTIONS)
+                );
+                assert_eq!(
+                    rook_attacks(&from, occupied),
+                    ray_attacks(&from, occupied, &ROOK_DIRECTIONS)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn toggling_twice_removes_a_piece() {
+        let mut bitboards = Bitboards::new();
+        bitboards.toggle(Color::Black, Kind::Queen, &location("d8"));
+        assert!(bitboards
+            .pieces(Color::Black, Kind::Queen)
+            .contains(&location("d8")));
+        assert_eq!(bitboards.occupied().count(), 1);
+        bitboards.toggle(Color::Black, Kind::Queen, &location("d8"));
+        assert!(bitboards.occupied().is_empty());
+    }
+}
@@ -1,8 +1,11 @@
+use crate::bitboard::{self, Bitboard, Bitboards};
+use crate::error::Error;
+use crate::zobrist;
 use regex::Regex;
 use std::collections::HashMap;
 use unicode_segmentation::UnicodeSegmentation;
 
-const FILE_CHARS: &'static str = "abcdefgh";
+const FILE_CHARS: &str = "abcdefgh";
 
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
 pub enum Color {
@@ -10,7 +13,38 @@ pub enum Color {
     Black,
 }
 
-#[derive(Debug, Eq, PartialEq)]
+impl Color {
+    /// A dense index for tables kept per color.
+    pub fn index(&self) -> usize {
+        return match self {
+            Color::White => 0,
+            Color::Black => 1,
+        };
+    }
+
+    pub fn opponent(&self) -> Color {
+        return match self {
+            Color::White => Color::Black,
+            Color::Black => Color::White,
+        };
+    }
+
+    pub(crate) fn home_rank(&self) -> u8 {
+        return match self {
+            Color::White => 0,
+            Color::Black => 7,
+        };
+    }
+
+    fn pawn_rank(&self) -> u8 {
+        return match self {
+            Color::White => 1,
+            Color::Black => 6,
+        };
+    }
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
 pub enum Kind {
     King,
     Queen,
@@ -20,7 +54,21 @@ pub enum Kind {
     Pawn,
 }
 
-fn kind_to_pgn(kind: &Kind) -> String {
+impl Kind {
+    /// A dense index for tables kept per kind.
+    pub fn index(&self) -> usize {
+        return match self {
+            Kind::King => 0,
+            Kind::Queen => 1,
+            Kind::Rook => 2,
+            Kind::Bishop => 3,
+            Kind::Knight => 4,
+            Kind::Pawn => 5,
+        };
+    }
+}
+
+pub fn kind_to_pgn(kind: &Kind) -> String {
     String::from(match kind {
         Kind::King => "K",
         Kind::Queen => "Q",
@@ -31,16 +79,18 @@ fn kind_to_pgn(kind: &Kind) -> String {
     })
 }
 
-fn pgn_to_kind(pgn: &str) -> Kind {
-    match pgn {
-        "K" => Kind::King,
-        "Q" => Kind::Queen,
-        "R" => Kind::Rook,
-        "B" => Kind::Bishop,
-        "N" => Kind::Knight,
-        "" => Kind::Pawn,
-        _ => panic!("Invalid piece kind: {}", pgn),
-    }
+const PROMOTION_KINDS: [Kind; 4] = [Kind::Queen, Kind::Rook, Kind::Bishop, Kind::Knight];
+
+pub fn pgn_to_kind(pgn: &str) -> Result<Kind, Error> {
+    return match pgn {
+        "K" => Ok(Kind::King),
+        "Q" => Ok(Kind::Queen),
+        "R" => Ok(Kind::Rook),
+        "B" => Ok(Kind::Bishop),
+        "N" => Ok(Kind::Knight),
+        "" => Ok(Kind::Pawn),
+        _ => Err(Error::InvalidPieceKind(String::from(pgn))),
+    };
 }
 
 #[derive(PartialEq, Eq, Hash, Copy, Clone, Debug)]
@@ -50,7 +100,15 @@ pub struct Location {
 }
 
 impl Location {
-    fn move_relative(&self, color: Color, rank_shift: i8, file_shift: i8) -> Option<Self> {
+    /// The colour of the square itself, so a1 is `Black` and h1 is `White`.
+    pub fn square_color(&self) -> Color {
+        if (self.rank + self.file) % 2 == 0 {
+            return Color::Black;
+        }
+        return Color::White;
+    }
+
+    pub fn move_relative(&self, color: Color, rank_shift: i8, file_shift: i8) -> Option<Self> {
         let rank_i8 = self.rank as i8;
         let file_i8 = self.file as i8;
         let new_rank = match color {
@@ -61,7 +119,7 @@ impl Location {
             Color::White => file_i8 + file_shift,
             Color::Black => file_i8 - file_shift,
         };
-        if new_rank < 0 || new_rank > 7 || new_file < 0 || new_file > 7 {
+        if !(0..8).contains(&new_rank) || !(0..8).contains(&new_file) {
             return None;
         }
         return Some(Self {
@@ -74,76 +132,194 @@ impl Location {
         return self.move_relative(color, 1, 0);
     }
 
-    fn left(&self, color: Color) -> Option<Self> {
-        return self.move_relative(color, 0, -1);
-    }
-
-    fn right(&self, color: Color) -> Option<Self> {
-        return self.move_relative(color, 0, 1);
-    }
-
-    fn pgn(&self) -> String {
+    pub fn pgn(&self) -> String {
         return format!(
             "{}{}",
             FILE_CHARS
                 .chars()
                 .nth(self.file as usize)
-                .expect(&format!("Invalid file {}", self.file)),
+                .unwrap_or_else(|| panic!("Invalid file {}", self.file)),
             self.rank + 1
         );
     }
 
-    fn parse_rank(rank: &str) -> u8 {
-        let result = rank.parse::<u8>().unwrap() - 1;
-        assert!(result >= 0 && result < 8, format!("Invalid rank: {}", rank));
-        return result;
+    pub fn parse_rank(rank: &str) -> Result<u8, Error> {
+        return match rank.parse::<u8>() {
+            Ok(result) if (1..=8).contains(&result) => Ok(result - 1),
+            _ => Err(Error::InvalidRank(String::from(rank))),
+        };
     }
 
-    fn parse_file(file: &str) -> u8 {
-        assert!(file.len() == 1, format!("Invalid file: {}", file));
-        FILE_CHARS
-            .find(file)
-            .expect(&format!("Invalid file: {}", file)) as u8
+    pub fn parse_file(file: &str) -> Result<u8, Error> {
+        if file.len() != 1 {
+            return Err(Error::InvalidFile(String::from(file)));
+        }
+        return match FILE_CHARS.find(file) {
+            Some(result) => Ok(result as u8),
+            None => Err(Error::InvalidFile(String::from(file))),
+        };
     }
 
-    fn parse_pgn(pgn: &str) -> Location {
-        assert!(pgn.len() == 2, format!("Invalid square: {}", pgn));
-        Location {
-            rank: Location::parse_rank(&pgn[1..2]),
-            file: Location::parse_file(&pgn[0..1]),
+    pub fn parse_pgn(pgn: &str) -> Result<Location, Error> {
+        if pgn.len() != 2 || !pgn.is_char_boundary(1) {
+            return Err(Error::InvalidSquare(String::from(pgn)));
         }
+        return Ok(Location {
+            rank: Location::parse_rank(&pgn[1..2])?,
+            file: Location::parse_file(&pgn[0..1])?,
+        });
     }
 }
 
-#[derive(Debug)]
+/// What `Board::apply_move` changed, with enough of the previous state to take the move back.
+#[derive(Clone, Debug)]
 pub struct Diff {
-    from: Option<Location>,
-    to: Option<Location>,
-    new_kind: Option<Kind>,
+    pub(crate) r#move: Move,
+    pub(crate) captured: Option<Box<dyn Piece>>,
+    pub(crate) castling: CastlingRights,
+    pub(crate) en_passant: Option<Location>,
+    pub(crate) halfmove_clock: u32,
+    /// `Board::hash` of the position before the move.
+    pub(crate) hash: u64,
+}
+
+impl Diff {
+    pub fn r#move(&self) -> &Move {
+        return &self.r#move;
+    }
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum CastlingSide {
+    Kingside,
+    Queenside,
+}
+
+impl CastlingSide {
+    fn rook_file(&self) -> u8 {
+        return match self {
+            CastlingSide::Kingside => 7,
+            CastlingSide::Queenside => 0,
+        };
+    }
+
+    pub(crate) fn king_dest_file(&self) -> u8 {
+        return match self {
+            CastlingSide::Kingside => 6,
+            CastlingSide::Queenside => 2,
+        };
+    }
+
+    fn rook_dest_file(&self) -> u8 {
+        return match self {
+            CastlingSide::Kingside => 5,
+            CastlingSide::Queenside => 3,
+        };
+    }
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct CastlingRights {
+    pub white_kingside: bool,
+    pub white_queenside: bool,
+    pub black_kingside: bool,
+    pub black_queenside: bool,
+}
+
+impl CastlingRights {
+    pub fn none() -> Self {
+        return Self {
+            white_kingside: false,
+            white_queenside: false,
+            black_kingside: false,
+            black_queenside: false,
+        };
+    }
+
+    pub fn get(&self, color: Color, side: CastlingSide) -> bool {
+        return match (color, side) {
+            (Color::White, CastlingSide::Kingside) => self.white_kingside,
+            (Color::White, CastlingSide::Queenside) => self.white_queenside,
+            (Color::Black, CastlingSide::Kingside) => self.black_kingside,
+            (Color::Black, CastlingSide::Queenside) => self.black_queenside,
+        };
+    }
+
+    fn set(&mut self, color: Color, side: CastlingSide, value: bool) {
+        match (color, side) {
+            (Color::White, CastlingSide::Kingside) => self.white_kingside = value,
+            (Color::White, CastlingSide::Queenside) => self.white_queenside = value,
+            (Color::Black, CastlingSide::Kingside) => self.black_kingside = value,
+            (Color::Black, CastlingSide::Queenside) => self.black_queenside = value,
+        }
+    }
 }
 
-#[derive(Debug)]
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
 pub enum Move {
     Simple(Location, Location),
+    Castle(Color, CastlingSide),
+    /// A pawn capture onto the en-passant target square; the captured pawn sits beside `from`.
+    EnPassant(Location, Location),
+    Promotion(Location, Location, Kind),
+}
+
+impl Move {
+    /// The move in the coordinate notation used by UCI and xboard: the from and to squares, then
+    /// any promotion piece in lowercase, like `e2e4` or `e7e8q`. Castling is written as the
+    /// king's move.
+    pub fn to_coordinates(&self) -> String {
+        return match self {
+            Move::Simple(from, to) | Move::EnPassant(from, to) => {
+                format!("{}{}", from.pgn(), to.pgn())
+            }
+            Move::Promotion(from, to, kind) => format!(
+                "{}{}{}",
+                from.pgn(),
+                to.pgn(),
+                kind_to_pgn(kind).to_lowercase()
+            ),
+            Move::Castle(color, side) => {
+                let rank = color.home_rank();
+                let from = Location {
+                    rank: rank,
+                    file: 4,
+                };
+                let to = Location {
+                    rank: rank,
+                    file: side.king_dest_file(),
+                };
+                format!("{}{}", from.pgn(), to.pgn())
+            }
+        };
+    }
 }
 
-pub trait Piece: core::fmt::Debug {
+/// Pieces are `Send` and `Sync` so that boards can be handed to a search thread.
+pub trait Piece: core::fmt::Debug + Send + Sync {
     fn color(&self) -> Color;
     fn kind(&self) -> Kind;
     fn location(&self) -> Location;
     fn set_location(&mut self, location: Location);
     fn possible_moves(&self, board: &Board) -> Vec<Move>;
     fn repr(&self) -> &str;
+    fn box_clone(&self) -> Box<dyn Piece>;
+}
+
+impl Clone for Box<dyn Piece> {
+    fn clone(&self) -> Self {
+        return self.box_clone();
+    }
 }
 
-#[derive(Debug)]
-struct Pawn {
+#[derive(Clone, Debug)]
+pub struct Pawn {
     color: Color,
     location: Location,
 }
 
 impl Pawn {
-    fn new(color: Color, location: Location) -> Self {
+    pub fn new(color: Color, location: Location) -> Self {
         return Self {
             color: color,
             location: location,
@@ -151,6 +327,44 @@ impl Pawn {
     }
 }
 
+/// Pushes every move for a pawn of `color` on `from`: single and double steps, captures and
+/// en passant.
+fn push_pawn_moves(board: &Board, result: &mut Vec<Move>, color: Color, from: Location) {
+    let pieces = &board.bitboards;
+    if let Some(forward1) = from.forward(color) {
+        if !pieces.occupied().contains(&forward1) {
+            push_pawn_move(result, color, from, forward1);
+            if from.rank == color.pawn_rank() {
+                if let Some(forward2) = forward1.forward(color) {
+                    if !pieces.occupied().contains(&forward2) {
+                        result.push(Move::Simple(from, forward2));
+                    }
+                }
+            }
+        }
+    }
+    let attacks = bitboard::pawn_attacks(color, &from);
+    for to in (attacks & pieces.color(color.opponent())).squares() {
+        push_pawn_move(result, color, from, to);
+    }
+    if let Some(en_passant) = board.en_passant {
+        if attacks.contains(&en_passant) {
+            result.push(Move::EnPassant(from, en_passant));
+        }
+    }
+}
+
+/// Pushes a pawn move to `to`, expanded into every promotion if `to` is on the last rank.
+fn push_pawn_move(result: &mut Vec<Move>, color: Color, from: Location, to: Location) {
+    if to.rank == color.opponent().home_rank() {
+        for kind in PROMOTION_KINDS.iter() {
+            result.push(Move::Promotion(from, to, *kind));
+        }
+    } else {
+        result.push(Move::Simple(from, to));
+    }
+}
+
 impl Piece for Pawn {
     fn color(&self) -> Color {
         return self.color;
@@ -170,38 +384,7 @@ impl Piece for Pawn {
 
     fn possible_moves(&self, board: &Board) -> Vec<Move> {
         let mut result = vec![];
-        if let Some(forward1) = self.location.forward(self.color) {
-            if let None = board.get_piece(&forward1) {
-                if let Some(forward2) = forward1.forward(self.color) {
-                    if let None = board.get_piece(&forward2) {
-                        result.append(&mut vec![
-                            Move::Simple(self.location, forward1),
-                            Move::Simple(self.location, forward2),
-                        ]);
-                    } else {
-                        result.push(Move::Simple(self.location, forward1));
-                    }
-                } else {
-                    result.push(Move::Simple(self.location, forward1));
-                }
-            }
-
-            if let Some(capture_left1) = forward1.left(self.color) {
-                if let Some(capture_left_piece1) = board.get_piece(&capture_left1) {
-                    if capture_left_piece1.color() != self.color {
-                        result.push(Move::Simple(self.location, capture_left1));
-                    }
-                }
-            }
-
-            if let Some(capture_right1) = forward1.right(self.color) {
-                if let Some(capture_right_piece1) = board.get_piece(&capture_right1) {
-                    if capture_right_piece1.color() != self.color {
-                        result.push(Move::Simple(self.location, capture_right1));
-                    }
-                }
-            }
-        }
+        push_pawn_moves(board, &mut result, self.color, self.location);
         return result;
     }
 
@@ -211,16 +394,20 @@ impl Piece for Pawn {
             Color::Black => "♟",
         };
     }
+
+    fn box_clone(&self) -> Box<dyn Piece> {
+        return Box::new(self.clone());
+    }
 }
 
-#[derive(Debug)]
-struct Knight {
+#[derive(Clone, Debug)]
+pub struct Knight {
     color: Color,
     location: Location,
 }
 
 impl Knight {
-    fn new(color: Color, location: Location) -> Self {
+    pub fn new(color: Color, location: Location) -> Self {
         return Self {
             color: color,
             location: location,
@@ -246,29 +433,8 @@ impl Piece for Knight {
     }
 
     fn possible_moves(&self, board: &Board) -> Vec<Move> {
-        let mut result: Vec<Move> = vec![];
-        let offsets: [(i8, i8); 8] = [
-            (1, 2),
-            (2, 1),
-            (2, -1),
-            (1, -2),
-            (-1, -2),
-            (-2, -1),
-            (-2, 1),
-            (-1, 2),
-        ];
-        for offset in offsets.iter() {
-            if let Some(location) = self.location.move_relative(self.color, offset.0, offset.1) {
-                if let Some(piece) = board.get_piece(&location) {
-                    if piece.color() != self.color {
-                        result.push(Move::Simple(self.location, location));
-                    }
-                } else {
-                    result.push(Move::Simple(self.location, location));
-                }
-            }
-        }
-        return result;
+        let attacks = bitboard::knight_attacks(&self.location);
+        return attack_moves(board, self.color, self.location, attacks);
     }
 
     fn repr(&self) -> &str {
@@ -277,16 +443,38 @@ impl Piece for Knight {
             Color::Black => "♞",
         };
     }
+
+    fn box_clone(&self) -> Box<dyn Piece> {
+        return Box::new(self.clone());
+    }
+}
+
+/// Pushes each castling move `color` has the right and room for. Castling out of or through
+/// check is rejected by `Board::legal_moves`.
+fn push_castles(board: &Board, result: &mut Vec<Move>, color: Color) {
+    for side in [CastlingSide::Kingside, CastlingSide::Queenside].iter() {
+        if board.can_castle(color, *side) {
+            result.push(Move::Castle(color, *side));
+        }
+    }
+}
+
+/// A simple move from `from` to each square in `attacks` that `color` does not already occupy.
+fn attack_moves(board: &Board, color: Color, from: Location, attacks: Bitboard) -> Vec<Move> {
+    return (attacks & !board.bitboards.color(color))
+        .squares()
+        .map(|to| Move::Simple(from, to))
+        .collect();
 }
 
-#[derive(Debug)]
-struct Bishop {
+#[derive(Clone, Debug)]
+pub struct Bishop {
     color: Color,
     location: Location,
 }
 
 impl Bishop {
-    fn new(color: Color, location: Location) -> Self {
+    pub fn new(color: Color, location: Location) -> Self {
         return Self {
             color: color,
             location: location,
@@ -312,28 +500,8 @@ impl Piece for Bishop {
     }
 
     fn possible_moves(&self, board: &Board) -> Vec<Move> {
-        let directions: [(i8, i8); 4] = [(1, 1), (1, -1), (-1, 1), (-1, -1)];
-        let mut result: Vec<Move> = vec![];
-
-        for direction in directions.iter() {
-            for distance in 1..8 {
-                if let Some(target_location) = self.location.move_relative(
-                    self.color,
-                    distance * direction.0,
-                    distance * direction.1,
-                ) {
-                    if let Some(target_piece) = board.get_piece(&target_location) {
-                        if target_piece.color() != self.color {
-                            result.push(Move::Simple(self.location, target_location));
-                        }
-                        break;
-                    } else {
-                        result.push(Move::Simple(self.location, target_location));
-                    }
-                }
-            }
-        }
-        return result;
+        let attacks = bitboard::bishop_attacks(&self.location, board.bitboards.occupied());
+        return attack_moves(board, self.color, self.location, attacks);
     }
 
     fn repr(&self) -> &str {
@@ -342,16 +510,20 @@ impl Piece for Bishop {
             Color::Black => "♝",
         };
     }
+
+    fn box_clone(&self) -> Box<dyn Piece> {
+        return Box::new(self.clone());
+    }
 }
 
-#[derive(Debug)]
-struct Rook {
+#[derive(Clone, Debug)]
+pub struct Rook {
     color: Color,
     location: Location,
 }
 
 impl Rook {
-    fn new(color: Color, location: Location) -> Self {
+    pub fn new(color: Color, location: Location) -> Self {
         return Self {
             color: color,
             location: location,
@@ -377,28 +549,8 @@ impl Piece for Rook {
     }
 
     fn possible_moves(&self, board: &Board) -> Vec<Move> {
-        let directions: [(i8, i8); 4] = [(0, 1), (1, 0), (0, -1), (-1, 0)];
-        let mut result: Vec<Move> = vec![];
-
-        for direction in directions.iter() {
-            for distance in 1..8 {
-                if let Some(target_location) = self.location.move_relative(
-                    self.color,
-                    distance * direction.0,
-                    distance * direction.1,
-                ) {
-                    if let Some(target_piece) = board.get_piece(&target_location) {
-                        if target_piece.color() != self.color {
-                            result.push(Move::Simple(self.location, target_location));
-                        }
-                        break;
-                    } else {
-                        result.push(Move::Simple(self.location, target_location));
-                    }
-                }
-            }
-        }
-        return result;
+        let attacks = bitboard::rook_attacks(&self.location, board.bitboards.occupied());
+        return attack_moves(board, self.color, self.location, attacks);
     }
 
     fn repr(&self) -> &str {
@@ -407,16 +559,20 @@ impl Piece for Rook {
             Color::Black => "♜",
         };
     }
+
+    fn box_clone(&self) -> Box<dyn Piece> {
+        return Box::new(self.clone());
+    }
 }
 
-#[derive(Debug)]
-struct Queen {
+#[derive(Clone, Debug)]
+pub struct Queen {
     color: Color,
     location: Location,
 }
 
 impl Queen {
-    fn new(color: Color, location: Location) -> Self {
+    pub fn new(color: Color, location: Location) -> Self {
         return Self {
             color: color,
             location: location,
@@ -442,37 +598,8 @@ impl Piece for Queen {
     }
 
     fn possible_moves(&self, board: &Board) -> Vec<Move> {
-        let directions: [(i8, i8); 8] = [
-            (0, 1),
-            (1, 0),
-            (0, -1),
-            (-1, 0),
-            (1, 1),
-            (1, -1),
-            (-1, 1),
-            (-1, -1),
-        ];
-        let mut result: Vec<Move> = vec![];
-
-        for direction in directions.iter() {
-            for distance in 1..8 {
-                if let Some(target_location) = self.location.move_relative(
-                    self.color,
-                    distance * direction.0,
-                    distance * direction.1,
-                ) {
-                    if let Some(target_piece) = board.get_piece(&target_location) {
-                        if target_piece.color() != self.color {
-                            result.push(Move::Simple(self.location, target_location));
-                        }
-                        break;
-                    } else {
-                        result.push(Move::Simple(self.location, target_location));
-                    }
-                }
-            }
-        }
-        return result;
+        let attacks = bitboard::queen_attacks(&self.location, board.bitboards.occupied());
+        return attack_moves(board, self.color, self.location, attacks);
     }
 
     fn repr(&self) -> &str {
@@ -481,16 +608,20 @@ impl Piece for Queen {
             Color::Black => "♛",
         };
     }
+
+    fn box_clone(&self) -> Box<dyn Piece> {
+        return Box::new(self.clone());
+    }
 }
 
-#[derive(Debug)]
-struct King {
+#[derive(Clone, Debug)]
+pub struct King {
     color: Color,
     location: Location,
 }
 
 impl King {
-    fn new(color: Color, location: Location) -> Self {
+    pub fn new(color: Color, location: Location) -> Self {
         return Self {
             color: color,
             location: location,
@@ -516,7 +647,10 @@ impl Piece for King {
     }
 
     fn possible_moves(&self, board: &Board) -> Vec<Move> {
-        return vec![];
+        let attacks = bitboard::king_attacks(&self.location);
+        let mut result = attack_moves(board, self.color, self.location, attacks);
+        push_castles(board, &mut result, self.color);
+        return result;
     }
 
     fn repr(&self) -> &str {
@@ -525,69 +659,372 @@ impl Piece for King {
             Color::Black => "♚",
         };
     }
+
+    fn box_clone(&self) -> Box<dyn Piece> {
+        return Box::new(self.clone());
+    }
 }
 
-pub fn piece_from_repr(repr: &str, location: Location) -> Option<Box<dyn Piece>> {
+pub fn piece_from_repr(repr: &str, location: Location) -> Result<Option<Box<dyn Piece>>, Error> {
     return match repr {
-        "♔" => Some(Box::new(King::new(Color::White, location))),
-        "♕" => Some(Box::new(Queen::new(Color::White, location))),
-        "♖" => Some(Box::new(Rook::new(Color::White, location))),
-        "♗" => Some(Box::new(Bishop::new(Color::White, location))),
-        "♘" => Some(Box::new(Knight::new(Color::White, location))),
-        "♙" => Some(Box::new(Pawn::new(Color::White, location))),
-        "♚" => Some(Box::new(King::new(Color::Black, location))),
-        "♛" => Some(Box::new(Queen::new(Color::Black, location))),
-        "♜" => Some(Box::new(Rook::new(Color::Black, location))),
-        "♝" => Some(Box::new(Bishop::new(Color::Black, location))),
-        "♞" => Some(Box::new(Knight::new(Color::Black, location))),
-        "♟" => Some(Box::new(Pawn::new(Color::Black, location))),
-        " " => None,
-        _ => panic!("Invalid repr character {}", repr),
+        "♔" => Ok(Some(Box::new(King::new(Color::White, location)))),
+        "♕" => Ok(Some(Box::new(Queen::new(Color::White, location)))),
+        "♖" => Ok(Some(Box::new(Rook::new(Color::White, location)))),
+        "♗" => Ok(Some(Box::new(Bishop::new(Color::White, location)))),
+        "♘" => Ok(Some(Box::new(Knight::new(Color::White, location)))),
+        "♙" => Ok(Some(Box::new(Pawn::new(Color::White, location)))),
+        "♚" => Ok(Some(Box::new(King::new(Color::Black, location)))),
+        "♛" => Ok(Some(Box::new(Queen::new(Color::Black, location)))),
+        "♜" => Ok(Some(Box::new(Rook::new(Color::Black, location)))),
+        "♝" => Ok(Some(Box::new(Bishop::new(Color::Black, location)))),
+        "♞" => Ok(Some(Box::new(Knight::new(Color::Black, location)))),
+        "♟" => Ok(Some(Box::new(Pawn::new(Color::Black, location)))),
+        " " => Ok(None),
+        _ => Err(Error::InvalidRepr(String::from(repr))),
+    };
+}
+
+#[derive(Debug, Eq, PartialEq)]
+pub enum FenError {
+    WrongFieldCount(usize),
+    InvalidPlacement(String),
+    InvalidActiveColor(String),
+    InvalidCastling(String),
+    InvalidEnPassant(String),
+    InvalidHalfmoveClock(String),
+    InvalidFullmoveNumber(String),
+}
+
+impl std::fmt::Display for FenError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        return match self {
+            FenError::WrongFieldCount(count) => {
+                write!(f, "Expected 4 or 6 FEN fields, found {}", count)
+            }
+            FenError::InvalidPlacement(field) => write!(f, "Invalid piece placement: {}", field),
+            FenError::InvalidActiveColor(field) => write!(f, "Invalid active color: {}", field),
+            FenError::InvalidCastling(field) => write!(f, "Invalid castling rights: {}", field),
+            FenError::InvalidEnPassant(field) => write!(f, "Invalid en passant square: {}", field),
+            FenError::InvalidHalfmoveClock(field) => {
+                write!(f, "Invalid halfmove clock: {}", field)
+            }
+            FenError::InvalidFullmoveNumber(field) => {
+                write!(f, "Invalid fullmove number: {}", field)
+            }
+        };
+    }
+}
+
+impl std::error::Error for FenError {}
+
+fn fen_char_to_piece(fen: char) -> Option<(Color, Kind)> {
+    let color = match fen.is_ascii_uppercase() {
+        true => Color::White,
+        false => Color::Black,
+    };
+    let kind = match fen.to_ascii_uppercase() {
+        'K' => Kind::King,
+        'Q' => Kind::Queen,
+        'R' => Kind::Rook,
+        'B' => Kind::Bishop,
+        'N' => Kind::Knight,
+        'P' => Kind::Pawn,
+        _ => return None,
+    };
+    return Some((color, kind));
+}
+
+fn piece_to_fen_char(piece: &dyn Piece) -> char {
+    let fen = match piece.kind() {
+        Kind::King => 'K',
+        Kind::Queen => 'Q',
+        Kind::Rook => 'R',
+        Kind::Bishop => 'B',
+        Kind::Knight => 'N',
+        Kind::Pawn => 'P',
     };
+    return match piece.color() {
+        Color::White => fen,
+        Color::Black => fen.to_ascii_lowercase(),
+    };
+}
+
+pub fn piece_from_kind(kind: Kind, color: Color, location: Location) -> Box<dyn Piece> {
+    return match kind {
+        Kind::King => Box::new(King::new(color, location)),
+        Kind::Queen => Box::new(Queen::new(color, location)),
+        Kind::Rook => Box::new(Rook::new(color, location)),
+        Kind::Bishop => Box::new(Bishop::new(color, location)),
+        Kind::Knight => Box::new(Knight::new(color, location)),
+        Kind::Pawn => Box::new(Pawn::new(color, location)),
+    };
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum GameStatus {
+    Ongoing,
+    Check,
+    /// The game is over and the given color has won.
+    Checkmate(Color),
+    Stalemate,
+    /// The game is over and drawn by rule, without either player having to claim it.
+    Draw(DrawReason),
+}
+
+/// Why a game is drawn, or may be drawn if the player to move claims it.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum DrawReason {
+    ThreefoldRepetition,
+    FiftyMoves,
+    FivefoldRepetition,
+    SeventyFiveMoves,
+    InsufficientMaterial,
+}
+
+impl std::fmt::Display for DrawReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        return match self {
+            DrawReason::ThreefoldRepetition => write!(f, "threefold repetition"),
+            DrawReason::FiftyMoves => write!(f, "the fifty-move rule"),
+            DrawReason::FivefoldRepetition => write!(f, "fivefold repetition"),
+            DrawReason::SeventyFiveMoves => write!(f, "the seventy-five-move rule"),
+            DrawReason::InsufficientMaterial => write!(f, "insufficient material"),
+        };
+    }
 }
 
+#[derive(Clone)]
 pub struct Board {
-    pub pieces: HashMap<Location, Box<dyn Piece>>,
-    pub to_move: Color,
+    pieces: HashMap<Location, Box<dyn Piece>>,
+    /// The same placement as `pieces`, kept in sync for fast move generation.
+    bitboards: Bitboards,
+    /// The Zobrist key of the whole position, updated as pieces are added and removed and as
+    /// moves change the side to move, castling rights and en-passant square.
+    hash: u64,
+    pub(crate) to_move: Color,
+    pub(crate) castling: CastlingRights,
+    /// The square a pawn skipped over with a double push on the previous move.
+    pub(crate) en_passant: Option<Location>,
+    /// Halfmoves since the last capture or pawn move.
+    pub(crate) halfmove_clock: u32,
+    /// Starts at 1 and is incremented after each Black move.
+    pub(crate) fullmove_number: u32,
+    history: Vec<Diff>,
+    undone: Vec<Move>,
 }
 
 impl Board {
     pub fn new() -> Self {
         return Board {
             pieces: HashMap::new(),
+            bitboards: Bitboards::new(),
+            hash: 0,
             to_move: Color::White,
+            castling: CastlingRights::none(),
+            en_passant: None,
+            halfmove_clock: 0,
+            fullmove_number: 1,
+            history: vec![],
+            undone: vec![],
+        };
+    }
+
+    /// A copy of the position without its move history, for trying out moves.
+    fn position_copy(&self) -> Self {
+        return Board {
+            pieces: self.pieces.clone(),
+            bitboards: self.bitboards.clone(),
+            hash: self.hash,
+            to_move: self.to_move,
+            castling: self.castling,
+            en_passant: self.en_passant,
+            halfmove_clock: self.halfmove_clock,
+            fullmove_number: self.fullmove_number,
+            history: vec![],
+            undone: vec![],
         };
     }
 
-    fn add_piece(&mut self, piece: Box<dyn Piece>) {
-        self.pieces.insert(piece.as_ref().location(), piece);
+    pub fn to_move(&self) -> Color {
+        return self.to_move;
+    }
+
+    pub fn castling(&self) -> CastlingRights {
+        return self.castling;
+    }
+
+    /// The square a pawn skipped over with a double push on the previous move.
+    pub fn en_passant(&self) -> Option<Location> {
+        return self.en_passant;
+    }
+
+    /// Halfmoves since the last capture or pawn move.
+    pub fn halfmove_clock(&self) -> u32 {
+        return self.halfmove_clock;
+    }
+
+    pub fn fullmove_number(&self) -> u32 {
+        return self.fullmove_number;
+    }
+
+    /// Every move applied so far that has not been undone, oldest first.
+    pub fn history(&self) -> &[Diff] {
+        return &self.history;
+    }
+
+    /// Places `piece` on its square, returning whatever piece it displaced.
+    pub(crate) fn add_piece(&mut self, piece: Box<dyn Piece>) -> Option<Box<dyn Piece>> {
+        let location = piece.location();
+        let displaced = self.remove_piece(&location);
+        self.bitboards
+            .toggle(piece.color(), piece.kind(), &location);
+        self.hash ^= zobrist::piece(piece.color(), piece.kind(), &location);
+        self.pieces.insert(location, piece);
+        return displaced;
+    }
+
+    pub(crate) fn remove_piece(&mut self, location: &Location) -> Option<Box<dyn Piece>> {
+        let piece = self.pieces.remove(location)?;
+        self.bitboards.toggle(piece.color(), piece.kind(), location);
+        self.hash ^= zobrist::piece(piece.color(), piece.kind(), location);
+        return Some(piece);
     }
 
-    fn remove_piece(&mut self, location: &Location) {
-        self.pieces.remove(location);
+    pub fn pieces(&self) -> impl Iterator<Item = &dyn Piece> {
+        return self.pieces.values().map(|piece| piece.as_ref());
     }
 
-    fn get_piece(&self, location: &Location) -> Option<&Box<dyn Piece>> {
-        return self.pieces.get(location);
+    pub(crate) fn bitboards(&self) -> &Bitboards {
+        return &self.bitboards;
     }
 
-    pub fn from_repr(repr: String) -> Self {
-        assert_eq!(repr.graphemes(true).count(), 64);
+    /// A 64-bit Zobrist key for the position, covering the pieces, side to move, castling rights
+    /// and any en-passant file on which a capture is possible. Equal positions always hash the
+    /// same, whatever moves led to them.
+    pub fn hash(&self) -> u64 {
+        return self.hash;
+    }
+
+    /// The part of the hash covering everything but the placement, which `make_move` swaps out.
+    fn state_hash(&self) -> u64 {
+        let mut result = zobrist::castling(&self.castling)
+            ^ zobrist::en_passant(self.en_passant, self.to_move, &self.bitboards);
+        if self.to_move == Color::Black {
+            result ^= zobrist::black_to_move();
+        }
+        return result;
+    }
+
+    /// Works out the hash from scratch, for positions set up other than by moves.
+    fn computed_hash(&self) -> u64 {
+        let mut result = self.state_hash();
+        for piece in self.pieces() {
+            result ^= zobrist::piece(piece.color(), piece.kind(), &piece.location());
+        }
+        return result;
+    }
+
+    pub fn get_piece(&self, location: &Location) -> Option<&dyn Piece> {
+        return self.pieces.get(location).map(|piece| piece.as_ref());
+    }
+
+    pub fn from_repr(repr: String) -> Result<Self, Error> {
+        let graphemes: Vec<&str> = repr.graphemes(true).collect();
+        if graphemes.len() != 64 {
+            return Err(Error::InvalidRepr(repr));
+        }
         let mut board = Self::new();
         for r in (0..8).rev() {
             for f in 0..8 {
                 let maybe_piece: Option<Box<dyn Piece>> = piece_from_repr(
-                    repr.graphemes(true)
-                        .nth((8 * (7 - r) + f) as usize)
-                        .expect("Invalid board repr length"),
+                    graphemes[(8 * (7 - r) + f) as usize],
                     Location { rank: r, file: f },
-                );
+                )?;
                 if let Some(piece) = maybe_piece {
                     board.add_piece(piece);
                 }
             }
         }
-        return board;
+        // A repr carries no move history, so assume any king and rook still on their home
+        // squares have not moved
+        for color in [Color::White, Color::Black].iter() {
+            for side in [CastlingSide::Kingside, CastlingSide::Queenside].iter() {
+                let king_home = Location {
+                    rank: color.home_rank(),
+                    file: 4,
+                };
+                let rook_home = Location {
+                    rank: color.home_rank(),
+                    file: side.rook_file(),
+                };
+                let has_rights = board.has_piece(&king_home, *color, Kind::King)
+                    && board.has_piece(&rook_home, *color, Kind::Rook);
+                board.castling.set(*color, *side, has_rights);
+            }
+        }
+        board.hash = board.computed_hash();
+        return Ok(board);
+    }
+
+    pub fn has_piece(&self, location: &Location, color: Color, kind: Kind) -> bool {
+        return match self.get_piece(location) {
+            Some(piece) => piece.color() == color && piece.kind() == kind,
+            None => false,
+        };
+    }
+
+    pub fn can_castle(&self, color: Color, side: CastlingSide) -> bool {
+        if !self.castling.get(color, side) {
+            return false;
+        }
+        let rank = color.home_rank();
+        if !self.has_piece(
+            &Location {
+                rank: rank,
+                file: 4,
+            },
+            color,
+            Kind::King,
+        ) || !self.has_piece(
+            &Location {
+                rank: rank,
+                file: side.rook_file(),
+            },
+            color,
+            Kind::Rook,
+        ) {
+            return false;
+        }
+        let (min_file, max_file) = match side {
+            CastlingSide::Kingside => (5, 6),
+            CastlingSide::Queenside => (1, 3),
+        };
+        for file in min_file..=max_file {
+            if self
+                .get_piece(&Location {
+                    rank: rank,
+                    file: file,
+                })
+                .is_some()
+            {
+                return false;
+            }
+        }
+        return true;
+    }
+
+    /// Drops any castling rights that depend on the king or rook having stayed on `location`.
+    fn revoke_castling_rights(&mut self, location: &Location) {
+        for color in [Color::White, Color::Black].iter() {
+            if location.rank != color.home_rank() {
+                continue;
+            }
+            for side in [CastlingSide::Kingside, CastlingSide::Queenside].iter() {
+                if location.file == 4 || location.file == side.rook_file() {
+                    self.castling.set(*color, *side, false);
+                }
+            }
+        }
     }
 
     pub fn to_str(&self) -> String {
@@ -595,7 +1032,7 @@ impl Board {
         for r in (0..8).rev() {
             for f in 0..8 {
                 match self.get_piece(&Location { rank: r, file: f }) {
-                    Some(piece) => result += piece.as_ref().repr(),
+                    Some(piece) => result += piece.repr(),
                     None => result += " ",
                 };
                 if f < 7 {
@@ -607,117 +1044,1660 @@ impl Board {
         return result;
     }
 
-    pub fn default() -> Self {
-        return Self::from_repr(String::from(concat!(
-            "♜♞♝♛♚♝♞♜",
-            "♟♟♟♟♟♟♟♟",
-            "        ",
-            "        ",
-            "        ",
-            "        ",
-            "♙♙♙♙♙♙♙♙",
-            "♖♘♗♕♔♗♘♖",
-        )));
-    }
-    pub fn possible_moves(&self) -> Vec<Move> {
-        let mut result: Vec<Move> = vec![];
-        for (_location, piece) in &self.pieces {
-            if piece.color() == self.to_move {
-                result.append(&mut piece.possible_moves(self));
-            }
+    /// Parses a position in Forsyth-Edwards Notation. The halfmove clock and fullmove number
+    /// may be omitted, in which case they default to 0 and 1.
+    pub fn from_fen(fen: &str) -> Result<Self, FenError> {
+        let fields: Vec<&str> = fen.split_whitespace().collect();
+        if fields.len() != 6 && fields.len() != 4 {
+            return Err(FenError::WrongFieldCount(fields.len()));
         }
+        let mut board = Self::new();
+
+        let ranks: Vec<&str> = fields[0].split('/').collect();
+        if ranks.len() != 8 {
+            return Err(FenError::InvalidPlacement(String::from(fields[0])));
+        }
+        for (i, rank_str) in ranks.iter().enumerate() {
+            let rank = 7 - i as u8;
+            let mut file: u8 = 0;
+            for fen_char in rank_str.chars() {
+                if let Some(empty) = fen_char.to_digit(10) {
+                    if empty == 0 || empty > 8 {
+                        return Err(FenError::InvalidPlacement(String::from(fields[0])));
+                    }
+                    file += empty as u8;
+                } else if let Some((color, kind)) = fen_char_to_piece(fen_char) {
+                    if file >= 8 {
+                        return Err(FenError::InvalidPlacement(String::from(fields[0])));
+                    }
+                    board.add_piece(piece_from_kind(
+                        kind,
+                        color,
+                        Location {
+                            rank: rank,
+                            file: file,
+                        },
+                    ));
+                    file += 1;
+                } else {
+                    return Err(FenError::InvalidPlacement(String::from(fields[0])));
+                }
+            }
+            if file != 8 {
+                return Err(FenError::InvalidPlacement(String::from(fields[0])));
+            }
+        }
+
+        board.to_move = match fields[1] {
+            "w" => Color::White,
+            "b" => Color::Black,
+            _ => return Err(FenError::InvalidActiveColor(String::from(fields[1]))),
+        };
+
+        if fields[2] != "-" {
+            for fen_char in fields[2].chars() {
+                let (color, side) = match fen_char {
+                    'K' => (Color::White, CastlingSide::Kingside),
+                    'Q' => (Color::White, CastlingSide::Queenside),
+                    'k' => (Color::Black, CastlingSide::Kingside),
+                    'q' => (Color::Black, CastlingSide::Queenside),
+                    _ => return Err(FenError::InvalidCastling(String::from(fields[2]))),
+                };
+                if board.castling.get(color, side) {
+                    return Err(FenError::InvalidCastling(String::from(fields[2])));
+                }
+                board.castling.set(color, side, true);
+            }
+        }
+
+        if fields[3] != "-" {
+            // The square skipped by the pawn that just moved, so behind the opponent's pawns
+            let en_passant_rank = match board.to_move {
+                Color::White => "6",
+                Color::Black => "3",
+            };
+            let en_passant_re: Regex = Regex::new(r"^[a-h][36]$").unwrap();
+            if !en_passant_re.is_match(fields[3]) || !fields[3].ends_with(en_passant_rank) {
+                return Err(FenError::InvalidEnPassant(String::from(fields[3])));
+            }
+            board.en_passant = Some(
+                Location::parse_pgn(fields[3])
+                    .map_err(|_| FenError::InvalidEnPassant(String::from(fields[3])))?,
+            );
+        }
+
+        if fields.len() == 6 {
+            board.halfmove_clock = fields[4]
+                .parse::<u32>()
+                .map_err(|_| FenError::InvalidHalfmoveClock(String::from(fields[4])))?;
+            board.fullmove_number = match fields[5].parse::<u32>() {
+                Ok(number) if number > 0 => number,
+                _ => return Err(FenError::InvalidFullmoveNumber(String::from(fields[5]))),
+            };
+        }
+        board.hash = board.computed_hash();
+        return Ok(board);
+    }
+
+    pub fn to_fen(&self) -> String {
+        let mut placement = String::new();
+        for r in (0..8).rev() {
+            let mut empty = 0;
+            for f in 0..8 {
+                match self.get_piece(&Location { rank: r, file: f }) {
+                    Some(piece) => {
+                        if empty > 0 {
+                            placement.push_str(&empty.to_string());
+                            empty = 0;
+                        }
+                        placement.push(piece_to_fen_char(piece));
+                    }
+                    None => empty += 1,
+                }
+            }
+            if empty > 0 {
+                placement.push_str(&empty.to_string());
+            }
+            if r > 0 {
+                placement.push('/');
+            }
+        }
+
+        let active_color = match self.to_move {
+            Color::White => "w",
+            Color::Black => "b",
+        };
+
+        let mut castling = String::new();
+        for (color, side, fen_char) in [
+            (Color::White, CastlingSide::Kingside, 'K'),
+            (Color::White, CastlingSide::Queenside, 'Q'),
+            (Color::Black, CastlingSide::Kingside, 'k'),
+            (Color::Black, CastlingSide::Queenside, 'q'),
+        ]
+        .iter()
+        {
+            if self.castling.get(*color, *side) {
+                castling.push(*fen_char);
+            }
+        }
+        if castling.is_empty() {
+            castling.push('-');
+        }
+
+        let en_passant = match self.en_passant {
+            Some(location) => location.pgn(),
+            None => String::from("-"),
+        };
+
+        return format!(
+            "{} {} {} {} {} {}",
+            placement,
+            active_color,
+            castling,
+            en_passant,
+            self.halfmove_clock,
+            self.fullmove_number
+        );
+    }
+
+    /// Whether any piece of color `by` attacks `location`, regardless of what occupies it.
+    pub fn is_attacked(&self, location: &Location, by: Color) -> bool {
+        return !self.attackers(location, by).is_empty();
+    }
+
+    /// The pieces of color `by` that attack `location`.
+    pub(crate) fn attackers(&self, location: &Location, by: Color) -> Bitboard {
+        return self
+            .bitboards
+            .attackers(location, by, self.bitboards.occupied());
+    }
+
+    pub fn king_location(&self, color: Color) -> Option<Location> {
+        return self.bitboards.pieces(color, Kind::King).first();
+    }
+
+    pub fn in_check(&self, color: Color) -> bool {
+        return match self.king_location(color) {
+            Some(location) => self.is_attacked(&location, color.opponent()),
+            None => false,
+        };
+    }
+
+    fn is_legal(&mut self, r#move: &Move) -> bool {
+        let color = self.to_move;
+        if let Move::Castle(_, side) = r#move {
+            // The king may not castle out of or through check; landing in check is caught below
+            let rank = color.home_rank();
+            let transit_file = (4 + side.king_dest_file()) / 2;
+            for file in [4, transit_file].iter() {
+                if self.is_attacked(
+                    &Location {
+                        rank: rank,
+                        file: *file,
+                    },
+                    color.opponent(),
+                ) {
+                    return false;
+                }
+            }
+        }
+        self.make_move(*r#move);
+        let result = !self.in_check(color);
+        self.unmake_move();
+        return result;
+    }
+
+    /// All moves for the side to move that do not leave its own king in check.
+    pub fn legal_moves(&self) -> Vec<Move> {
+        return self.position_copy().legal_moves_in_place();
+    }
+
+    /// The same as `legal_moves`, but tries each move out on this board rather than on a copy.
+    /// The board is left as it was.
+    pub(crate) fn legal_moves_in_place(&mut self) -> Vec<Move> {
+        let mut result: Vec<Move> = vec![];
+        for r#move in self.possible_moves() {
+            if self.is_legal(&r#move) {
+                result.push(r#move);
+            }
+        }
+        return result;
+    }
+
+    /// Counts the leaf nodes of the legal move tree `depth` plies deep.
+    pub fn perft(&self, depth: u32) -> u64 {
+        return self.position_copy().perft_recursive(depth);
+    }
+
+    /// The perft node count below each legal move, for tracking down move generation bugs.
+    /// Empty at depth 0, where no moves are made.
+    pub fn divide(&self, depth: u32) -> Vec<(Move, u64)> {
+        let mut result: Vec<(Move, u64)> = vec![];
+        if depth == 0 {
+            return result;
+        }
+        let mut board = self.position_copy();
+        for r#move in board.legal_moves_in_place() {
+            board.make_move(r#move);
+            let nodes = board.perft_recursive(depth - 1);
+            board.unmake_move();
+            result.push((r#move, nodes));
+        }
+        return result;
+    }
+
+    fn perft_recursive(&mut self, depth: u32) -> u64 {
+        if depth == 0 {
+            return 1;
+        }
+        let moves = self.legal_moves_in_place();
+        if depth == 1 {
+            return moves.len() as u64;
+        }
+        let mut nodes = 0;
+        for r#move in moves {
+            self.make_move(r#move);
+            nodes += self.perft_recursive(depth - 1);
+            self.unmake_move();
+        }
+        return nodes;
+    }
+
+    /// Pseudo-legal moves for the side to move; see `legal_moves` for the filtered list.
+    /// These are generated from the bitboards, the same way as by `Piece::possible_moves`.
+    pub fn possible_moves(&self) -> Vec<Move> {
+        let mut result: Vec<Move> = Vec::with_capacity(64);
+        let color = self.to_move;
+        let pieces = &self.bitboards;
+        let occupied = pieces.occupied();
+
+        for from in pieces.pieces(color, Kind::Pawn).squares() {
+            push_pawn_moves(self, &mut result, color, from);
+        }
+
+        for kind in [
+            Kind::Knight,
+            Kind::Bishop,
+            Kind::Rook,
+            Kind::Queen,
+            Kind::King,
+        ]
+        .iter()
+        {
+            for from in pieces.pieces(color, *kind).squares() {
+                let attacks = match kind {
+                    Kind::Knight => bitboard::knight_attacks(&from),
+                    Kind::Bishop => bitboard::bishop_attacks(&from, occupied),
+                    Kind::Rook => bitboard::rook_attacks(&from, occupied),
+                    Kind::Queen => bitboard::queen_attacks(&from, occupied),
+                    _ => bitboard::king_attacks(&from),
+                };
+                result.append(&mut attack_moves(self, color, from, attacks));
+            }
+        }
+
+        push_castles(self, &mut result, color);
         return result;
     }
+
+    /// Plays `move` and records it so it can be taken back with `undo_move`. Any moves that were
+    /// undone can no longer be redone afterwards.
     pub fn apply_move(&mut self, r#move: Move) {
+        self.undone.clear();
+        self.make_move(r#move);
+    }
+
+    /// Plays `move` without touching the moves available to `redo_move`, for code that always
+    /// takes its moves back again, such as search.
+    pub(crate) fn make_move(&mut self, r#move: Move) {
+        let mut diff = Diff {
+            r#move: r#move,
+            captured: None,
+            castling: self.castling,
+            en_passant: self.en_passant,
+            halfmove_clock: self.halfmove_clock,
+            hash: self.hash,
+        };
+        // The en-passant key depends on the pawns, so take it out before any of them move
+        self.hash ^= self.state_hash();
+        let resets_clock = match r#move {
+            Move::Simple(from, to) => {
+                self.has_piece(&from, self.to_move, Kind::Pawn) || self.get_piece(&to).is_some()
+            }
+            Move::Castle(_, _) => false,
+            Move::EnPassant(_, _) | Move::Promotion(_, _, _) => true,
+        };
+        self.en_passant = None;
         match r#move {
             Move::Simple(from, to) => {
                 let mut piece = self
-                    .pieces
-                    .remove(&from)
-                    .expect(&format!("No piece at {}", from.pgn()));
+                    .remove_piece(&from)
+                    .unwrap_or_else(|| panic!("No piece at {}", from.pgn()));
+                if piece.kind() == Kind::Pawn
+                    && from.rank == piece.color().pawn_rank()
+                    && (from.rank as i8 - to.rank as i8).abs() == 2
+                {
+                    self.en_passant = Some(Location {
+                        rank: (from.rank + to.rank) / 2,
+                        file: from.file,
+                    });
+                }
                 piece.set_location(to);
-                self.pieces.insert(to, piece);
+                diff.captured = self.add_piece(piece);
+                self.revoke_castling_rights(&from);
+                self.revoke_castling_rights(&to);
+            }
+            Move::Castle(color, side) => {
+                let rank = color.home_rank();
+                let king_from = Location {
+                    rank: rank,
+                    file: 4,
+                };
+                let rook_from = Location {
+                    rank: rank,
+                    file: side.rook_file(),
+                };
+                let mut king = self
+                    .remove_piece(&king_from)
+                    .unwrap_or_else(|| panic!("No king at {}", king_from.pgn()));
+                let mut rook = self
+                    .remove_piece(&rook_from)
+                    .unwrap_or_else(|| panic!("No rook at {}", rook_from.pgn()));
+                king.set_location(Location {
+                    rank: rank,
+                    file: side.king_dest_file(),
+                });
+                rook.set_location(Location {
+                    rank: rank,
+                    file: side.rook_dest_file(),
+                });
+                self.add_piece(king);
+                self.add_piece(rook);
+                self.revoke_castling_rights(&king_from);
+            }
+            Move::EnPassant(from, to) => {
+                let mut pawn = self
+                    .remove_piece(&from)
+                    .unwrap_or_else(|| panic!("No piece at {}", from.pgn()));
+                pawn.set_location(to);
+                self.add_piece(pawn);
+                diff.captured = self.remove_piece(&Location {
+                    rank: from.rank,
+                    file: to.file,
+                });
+            }
+            Move::Promotion(from, to, kind) => {
+                let pawn = self
+                    .remove_piece(&from)
+                    .unwrap_or_else(|| panic!("No piece at {}", from.pgn()));
+                diff.captured = self.add_piece(piece_from_kind(kind, pawn.color(), to));
+                self.revoke_castling_rights(&to);
             }
         }
-        self.to_move = match &self.to_move {
-            Color::Black => Color::White,
-            Color::White => Color::Black,
+        if resets_clock {
+            self.halfmove_clock = 0;
+        } else {
+            self.halfmove_clock += 1;
+        }
+        if self.to_move == Color::Black {
+            self.fullmove_number += 1;
+        }
+        self.to_move = self.to_move.opponent();
+        self.hash ^= self.state_hash();
+        self.history.push(diff);
+    }
+
+    /// Takes back the last move, returning it, or `None` if there is nothing to undo.
+    pub fn undo_move(&mut self) -> Option<Move> {
+        let r#move = self.unmake_move()?;
+        self.undone.push(r#move);
+        return Some(r#move);
+    }
+
+    /// Takes back the last move like `undo_move`, but without offering it to `redo_move`.
+    pub(crate) fn unmake_move(&mut self) -> Option<Move> {
+        let diff = self.history.pop()?;
+        self.to_move = self.to_move.opponent();
+        if self.to_move == Color::Black {
+            self.fullmove_number -= 1;
+        }
+        match diff.r#move {
+            Move::Simple(from, to) | Move::EnPassant(from, to) => {
+                let mut piece = self
+                    .remove_piece(&to)
+                    .unwrap_or_else(|| panic!("No piece at {}", to.pgn()));
+                piece.set_location(from);
+                self.add_piece(piece);
+            }
+            Move::Castle(color, side) => {
+                let rank = color.home_rank();
+                let mut king = self
+                    .remove_piece(&Location {
+                        rank: rank,
+                        file: side.king_dest_file(),
+                    })
+                    .expect("No castled king to undo");
+                let mut rook = self
+                    .remove_piece(&Location {
+                        rank: rank,
+                        file: side.rook_dest_file(),
+                    })
+                    .expect("No castled rook to undo");
+                king.set_location(Location {
+                    rank: rank,
+                    file: 4,
+                });
+                rook.set_location(Location {
+                    rank: rank,
+                    file: side.rook_file(),
+                });
+                self.add_piece(king);
+                self.add_piece(rook);
+            }
+            Move::Promotion(from, to, _) => {
+                self.remove_piece(&to);
+                self.add_piece(Box::new(Pawn::new(self.to_move, from)));
+            }
+        }
+        // Captured pieces still know their own square, which differs from `to` for en passant
+        if let Some(captured) = diff.captured {
+            self.add_piece(captured);
+        }
+        self.castling = diff.castling;
+        self.en_passant = diff.en_passant;
+        self.halfmove_clock = diff.halfmove_clock;
+        self.hash = diff.hash;
+        return Some(diff.r#move);
+    }
+
+    /// Replays the most recently undone move, returning it, or `None` if there is nothing to redo.
+    pub fn redo_move(&mut self) -> Option<Move> {
+        let r#move = self.undone.pop()?;
+        self.make_move(r#move);
+        return Some(r#move);
+    }
+
+    /// Which part of `from` must be written so that no other piece of the same kind that can
+    /// also reach `to` is confused with the one moving.
+    fn disambiguation(&self, kind: Kind, from: &Location, to: &Location) -> String {
+        let mut ambiguous = false;
+        let mut shares_file = false;
+        let mut shares_rank = false;
+        for r#move in self.legal_moves() {
+            if let Move::Simple(other_from, other_to) = r#move {
+                if other_to != *to
+                    || other_from == *from
+                    || !self.has_piece(&other_from, self.to_move, kind)
+                {
+                    continue;
+                }
+                ambiguous = true;
+                shares_file |= other_from.file == from.file;
+                shares_rank |= other_from.rank == from.rank;
+            }
+        }
+        if !ambiguous {
+            return String::new();
+        }
+        if !shares_file {
+            return String::from(&from.pgn()[0..1]);
+        }
+        if !shares_rank {
+            return String::from(&from.pgn()[1..2]);
+        }
+        return from.pgn();
+    }
+
+    pub fn is_capture(&self, r#move: &Move) -> bool {
+        return match r#move {
+            Move::Castle(_, _) => false,
+            Move::EnPassant(_, _) => true,
+            Move::Simple(_, to) | Move::Promotion(_, to, _) => self.get_piece(to).is_some(),
         };
     }
 
+    /// Formats `move` in Standard Algebraic Notation, including check and mate suffixes.
     pub fn to_pgn(&self, r#move: &Move) -> String {
-        match r#move {
-            Move::Simple(from, to) => {
-                if let Some(move_piece) = self.get_piece(from) {
-                    let mut result = String::new();
-                    result.push_str(&kind_to_pgn(&move_piece.as_ref().kind()));
-                    result.push_str(&to.pgn());
-                    return result;
+        let mut result = match r#move {
+            Move::Castle(_, CastlingSide::Kingside) => String::from("O-O"),
+            Move::Castle(_, CastlingSide::Queenside) => String::from("O-O-O"),
+            Move::Simple(from, to) | Move::EnPassant(from, to) | Move::Promotion(from, to, _) => {
+                let kind = match self.get_piece(from) {
+                    Some(move_piece) => move_piece.kind(),
+                    None => panic!("No piece at {}", from.pgn()),
+                };
+                let capture = self.is_capture(r#move);
+                let mut result = String::new();
+                if kind == Kind::Pawn {
+                    if capture {
+                        result.push_str(&from.pgn()[0..1]);
+                    }
                 } else {
-                    panic!("No piece at {}", from.pgn());
+                    result.push_str(&kind_to_pgn(&kind));
+                    result.push_str(&self.disambiguation(kind, from, to));
+                }
+                if capture {
+                    result.push('x');
+                }
+                result.push_str(&to.pgn());
+                if let Move::Promotion(_, _, promotion_kind) = r#move {
+                    result.push('=');
+                    result.push_str(&kind_to_pgn(promotion_kind));
                 }
+                result
+            }
+        };
+        let mut board = self.position_copy();
+        board.make_move(*r#move);
+        if board.in_check(board.to_move) {
+            if board.legal_moves().is_empty() {
+                result.push('#');
+            } else {
+                result.push('+');
+            }
+        }
+        return result;
+    }
+
+    /// How the game stands for the player to move. Draws that must be claimed are not reported
+    /// here; see `claimable_draw`.
+    pub fn status(&self) -> GameStatus {
+        let in_check = self.in_check(self.to_move);
+        if self.legal_moves().is_empty() {
+            if in_check {
+                return GameStatus::Checkmate(self.to_move.opponent());
+            }
+            return GameStatus::Stalemate;
+        }
+        if self.is_insufficient_material() {
+            return GameStatus::Draw(DrawReason::InsufficientMaterial);
+        }
+        if self.repetitions() >= 5 {
+            return GameStatus::Draw(DrawReason::FivefoldRepetition);
+        }
+        if self.halfmove_clock >= 150 {
+            return GameStatus::Draw(DrawReason::SeventyFiveMoves);
+        }
+        if in_check {
+            return GameStatus::Check;
+        }
+        return GameStatus::Ongoing;
+    }
+
+    /// Whether neither side can possibly checkmate: bare kings, a single minor piece against a
+    /// bare king, or any number of bishops that all stand on squares of the same colour.
+    pub fn is_insufficient_material(&self) -> bool {
+        let mut knights = 0;
+        let mut bishop_squares: Vec<Color> = vec![];
+        for piece in self.pieces() {
+            match piece.kind() {
+                Kind::King => {}
+                Kind::Knight => knights += 1,
+                Kind::Bishop => bishop_squares.push(piece.location().square_color()),
+                Kind::Pawn | Kind::Rook | Kind::Queen => return false,
+            }
+        }
+        if knights + bishop_squares.len() <= 1 {
+            return true;
+        }
+        return knights == 0 && bishop_squares.windows(2).all(|pair| pair[0] == pair[1]);
+    }
+
+    /// A draw the player to move may claim, if the current position has occurred three times or
+    /// fifty moves have passed by each side without a capture or pawn move.
+    pub fn claimable_draw(&self) -> Option<DrawReason> {
+        if self.repetitions() >= 3 {
+            return Some(DrawReason::ThreefoldRepetition);
+        }
+        if self.halfmove_clock >= 100 {
+            return Some(DrawReason::FiftyMoves);
+        }
+        return None;
+    }
+
+    /// How many times the current position has occurred, counting this one. Only positions since
+    /// the last capture or pawn move can repeat, and only those with the same side to move.
+    pub fn repetitions(&self) -> usize {
+        let hash = self.hash();
+        let earlier = self
+            .history
+            .iter()
+            .rev()
+            .take(self.halfmove_clock as usize)
+            .skip(1)
+            .step_by(2)
+            .filter(|diff| diff.hash == hash)
+            .count();
+        return earlier + 1;
+    }
+
+    /// Parses a legal move in coordinate notation; see `Move::to_coordinates`.
+    pub fn parse_coordinate_move(&self, text: &str) -> Result<Move, Error> {
+        let coordinate_re: Regex = Regex::new(r"^[a-h][1-8][a-h][1-8][qrbn]?$").unwrap();
+        let text = text.trim();
+        if !coordinate_re.is_match(text) {
+            return Err(Error::UnparseableMove(String::from(text)));
+        }
+        for r#move in self.legal_moves() {
+            if r#move.to_coordinates() == text {
+                return Ok(r#move);
             }
         }
+        return Err(Error::IllegalMove(String::from(text)));
     }
 
-    pub fn parse_pgn_move(&self, pgn: &str) -> Move {
-        let simple_re: Regex = Regex::new(r"^([NBRQK]?)([a-h]?)([1-8]?)x?([a-h][1-8])$").unwrap();
+    pub fn parse_pgn_move(&self, pgn: &str) -> Result<Move, Error> {
+        let legal_moves = self.legal_moves();
+        let pgn = pgn.trim_end_matches(&['+', '#'][..]);
+
+        let castle_re: Regex = Regex::new(r"^(O-O|0-0)(-O|-0)?$").unwrap();
+        if let Some(cap) = castle_re.captures(pgn) {
+            let side = match cap.get(2) {
+                Some(_) => CastlingSide::Queenside,
+                None => CastlingSide::Kingside,
+            };
+            for r#move in legal_moves {
+                if let Move::Castle(_, move_side) = r#move {
+                    if move_side == side {
+                        return Ok(r#move);
+                    }
+                }
+            }
+            return Err(Error::IllegalMove(String::from(pgn)));
+        }
+
+        let simple_re: Regex =
+            Regex::new(r"^([NBRQK]?)([a-h]?)([1-8]?)x?([a-h][1-8])(?:=?([NBRQ]))?$").unwrap();
         if simple_re.is_match(pgn) {
             let cap = simple_re.captures_iter(pgn).next().expect("");
-            let kind = pgn_to_kind(&cap[1]);
+            let kind = pgn_to_kind(&cap[1])?;
             let source_file: Option<u8> = match &cap[2] {
                 "" => None,
-                file_char => Some(FILE_CHARS.find(file_char).expect("") as u8),
+                file_char => Some(Location::parse_file(file_char)?),
             };
             let source_rank: Option<u8> = match &cap[3] {
                 "" => None,
-                rank_char => Some(rank_char.parse::<u8>().unwrap() - 1),
+                rank_char => Some(Location::parse_rank(rank_char)?),
             };
-            let dest_loc = Location::parse_pgn(&cap[4]);
-            let mut candidate_pieces: Vec<&Box<dyn Piece>> = vec![];
-            for (_, piece) in &self.pieces {
-                if piece.color() != self.to_move {
-                    continue;
-                }
-                if piece.kind() != kind {
+            let dest_loc = Location::parse_pgn(&cap[4])?;
+            let promotion_kind: Option<Kind> = match cap.get(5) {
+                Some(promotion) => Some(pgn_to_kind(promotion.as_str())?),
+                None => None,
+            };
+            let mut candidate_moves: Vec<Move> = vec![];
+            for r#move in legal_moves {
+                let from = match (r#move, promotion_kind) {
+                    (Move::Simple(from, to), None) | (Move::EnPassant(from, to), None)
+                        if to == dest_loc =>
+                    {
+                        from
+                    }
+                    (Move::Promotion(from, to, move_kind), Some(kind))
+                        if to == dest_loc && move_kind == kind =>
+                    {
+                        from
+                    }
+                    _ => continue,
+                };
+                if !self.has_piece(&from, self.to_move, kind) {
                     continue;
                 }
                 if let Some(from_file) = source_file {
-                    if from_file != piece.location().file {
+                    if from_file != from.file {
                         continue;
                     }
                 }
                 if let Some(from_rank) = source_rank {
-                    if from_rank != piece.location().rank {
+                    if from_rank != from.rank {
                         continue;
                     }
                 }
-                let mut can_move = false;
-                for r#move in piece.possible_moves(self) {
-                    if let Move::Simple(from, to) = r#move {
-                        if to == dest_loc {
-                            can_move = true;
-                            break;
-                        }
-                    }
-                }
-                if !can_move {
-                    continue;
-                }
-                candidate_pieces.push(piece);
+                candidate_moves.push(r#move);
             }
-            println!("{:?}", candidate_pieces);
-            assert!(
-                candidate_pieces.len() > 0,
-                format!("No pieces can make the move: {}", pgn)
-            );
-            assert!(
-                candidate_pieces.len() == 1,
-                format!("Move is ambiguous: {}", pgn)
+            return match candidate_moves.len() {
+                0 => Err(Error::IllegalMove(String::from(pgn))),
+                1 => Ok(candidate_moves[0]),
+                _ => Err(Error::AmbiguousMove(String::from(pgn))),
+            };
+        }
+        return Err(Error::UnparseableMove(String::from(pgn)));
+    }
+}
+
+impl Default for Board {
+    /// The standard starting position.
+    fn default() -> Self {
+        return Self::from_repr(String::from(concat!(
+            "♜♞♝♛♚♝♞♜",
+            "♟♟♟♟♟♟♟♟",
+            "        ",
+            "        ",
+            "        ",
+            "        ",
+            "♙♙♙♙♙♙♙♙",
+            "♖♘♗♕♔♗♘♖",
+        )))
+        .expect("The starting position repr is valid");
+    }
+}
+
+impl std::fmt::Debug for Board {
+    /// The position as FEN, leaving out the move history.
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        return f.debug_tuple("Board").field(&self.to_fen()).finish();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn board(rows: [&str; 8], to_move: Color) -> Board {
+        let mut board = Board::from_repr(rows.concat()).unwrap();
+        board.to_move = to_move;
+        board.hash = board.computed_hash();
+        return board;
+    }
+
+    fn pawn_moves(board: &Board, from: &str) -> Vec<Move> {
+        let from = Location::parse_pgn(from).unwrap();
+        let piece = board.get_piece(&from).expect("No piece at source square");
+        assert_eq!(piece.kind(), Kind::Pawn);
+        return piece.possible_moves(board);
+    }
+
+    fn simple(from: &str, to: &str) -> Move {
+        return Move::Simple(
+            Location::parse_pgn(from).unwrap(),
+            Location::parse_pgn(to).unwrap(),
+        );
+    }
+
+    #[test]
+    fn white_pawn_double_steps_from_starting_rank() {
+        let board = Board::default();
+        let moves = pawn_moves(&board, "e2");
+        assert_eq!(moves.len(), 2);
+        assert!(moves.contains(&simple("e2", "e3")));
+        assert!(moves.contains(&simple("e2", "e4")));
+    }
+
+    #[test]
+    fn black_pawn_double_steps_from_starting_rank() {
+        let board = Board {
+            to_move: Color::Black,
+            ..Board::default()
+        };
+        let moves = pawn_moves(&board, "d7");
+        assert_eq!(moves.len(), 2);
+        assert!(moves.contains(&simple("d7", "d6")));
+        assert!(moves.contains(&simple("d7", "d5")));
+    }
+
+    #[test]
+    fn white_pawn_single_steps_off_starting_rank() {
+        let board = board(
+            [
+                "    ♚   ",
+                "        ",
+                "        ",
+                "        ",
+                "    ♙   ",
+                "        ",
+                "        ",
+                "    ♔   ",
+            ],
+            Color::White,
+        );
+        assert_eq!(pawn_moves(&board, "e4"), vec![simple("e4", "e5")]);
+    }
+
+    #[test]
+    fn black_pawn_single_steps_off_starting_rank() {
+        let board = board(
+            [
+                "    ♚   ",
+                "        ",
+                "        ",
+                "    ♟   ",
+                "        ",
+                "        ",
+                "        ",
+                "    ♔   ",
+            ],
+            Color::Black,
+        );
+        assert_eq!(pawn_moves(&board, "e5"), vec![simple("e5", "e4")]);
+    }
+
+    #[test]
+    fn blocked_pawns_cannot_advance() {
+        let board = board(
+            [
+                "    ♚   ",
+                "  ♟     ",
+                "  ♘     ",
+                "        ",
+                "      ♞ ",
+                "        ",
+                "      ♙ ",
+                "    ♔   ",
+            ],
+            Color::White,
+        );
+        assert_eq!(pawn_moves(&board, "g2"), vec![simple("g2", "g3")]);
+        assert!(pawn_moves(&board, "c7").is_empty());
+    }
+
+    #[test]
+    fn pawns_capture_diagonally_forward() {
+        let board = board(
+            [
+                "    ♚   ",
+                "        ",
+                "        ",
+                "   ♟ ♙  ",
+                "    ♙   ",
+                "        ",
+                "        ",
+                "    ♔   ",
+            ],
+            Color::White,
+        );
+        let white_moves = pawn_moves(&board, "e4");
+        assert_eq!(white_moves.len(), 2);
+        assert!(white_moves.contains(&simple("e4", "e5")));
+        assert!(white_moves.contains(&simple("e4", "d5")));
+
+        let black_moves = pawn_moves(&board, "d5");
+        assert_eq!(black_moves.len(), 2);
+        assert!(black_moves.contains(&simple("d5", "d4")));
+        assert!(black_moves.contains(&simple("d5", "e4")));
+    }
+
+    #[test]
+    fn double_step_sets_en_passant_square() {
+        let mut board = Board::default();
+        board.apply_move(simple("e2", "e4"));
+        assert_eq!(board.en_passant, Some(Location::parse_pgn("e3").unwrap()));
+        board.apply_move(simple("g8", "f6"));
+        assert_eq!(board.en_passant, None);
+        board.apply_move(simple("e4", "e5"));
+        assert_eq!(board.en_passant, None);
+        board.apply_move(simple("d7", "d5"));
+        assert_eq!(board.en_passant, Some(Location::parse_pgn("d6").unwrap()));
+
+        let moves = pawn_moves(&board, "e5");
+        assert!(moves.contains(&Move::EnPassant(
+            Location::parse_pgn("e5").unwrap(),
+            Location::parse_pgn("d6").unwrap()
+        )));
+    }
+
+    #[test]
+    fn black_can_capture_en_passant() {
+        let mut board = board(
+            [
+                "    ♚   ",
+                "        ",
+                "        ",
+                "        ",
+                "     ♟  ",
+                "        ",
+                "    ♙   ",
+                "    ♔   ",
+            ],
+            Color::White,
+        );
+        board.apply_move(simple("e2", "e4"));
+        let moves = pawn_moves(&board, "f4");
+        assert_eq!(moves.len(), 2);
+        assert!(moves.contains(&simple("f4", "f3")));
+        assert!(moves.contains(&Move::EnPassant(
+            Location::parse_pgn("f4").unwrap(),
+            Location::parse_pgn("e3").unwrap()
+        )));
+
+        board.apply_move(board.parse_pgn_move("fxe3").unwrap());
+        assert!(board
+            .get_piece(&Location::parse_pgn("e4").unwrap())
+            .is_none());
+        assert!(board.has_piece(
+            &Location::parse_pgn("e3").unwrap(),
+            Color::Black,
+            Kind::Pawn
+        ));
+    }
+
+    #[test]
+    fn pawns_promote_on_last_rank() {
+        let board = board(
+            [
+                "       ♚",
+                "  ♙     ",
+                "        ",
+                "        ",
+                "        ",
+                "        ",
+                "     ♟  ",
+                "♔       ",
+            ],
+            Color::White,
+        );
+        let white_moves = pawn_moves(&board, "c7");
+        assert_eq!(white_moves.len(), 4);
+        for kind in PROMOTION_KINDS.iter() {
+            assert!(white_moves.contains(&Move::Promotion(
+                Location::parse_pgn("c7").unwrap(),
+                Location::parse_pgn("c8").unwrap(),
+                *kind
+            )));
+        }
+
+        let black_moves = pawn_moves(&board, "f2");
+        assert_eq!(black_moves.len(), 4);
+        assert!(black_moves.contains(&Move::Promotion(
+            Location::parse_pgn("f2").unwrap(),
+            Location::parse_pgn("f1").unwrap(),
+            Kind::Knight
+        )));
+    }
+
+    #[test]
+    fn fen_round_trips() {
+        let start = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+        assert_eq!(Board::default().to_fen(), start);
+        assert_eq!(Board::from_fen(start).unwrap().to_fen(), start);
+        assert_eq!(
+            format!("{:?}", Board::default()),
+            format!("Board({:?})", start)
+        );
+
+        let kiwipete = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+        assert_eq!(Board::from_fen(kiwipete).unwrap().to_fen(), kiwipete);
+    }
+
+    #[test]
+    fn fen_tracks_en_passant_and_clocks() {
+        let mut board = Board::default();
+        board.apply_move(board.parse_pgn_move("e4").unwrap());
+        assert_eq!(
+            board.to_fen(),
+            "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1"
+        );
+        board.apply_move(board.parse_pgn_move("Nf6").unwrap());
+        assert_eq!(
+            board.to_fen(),
+            "rnbqkb1r/pppppppp/5n2/8/4P3/8/PPPP1PPP/RNBQKBNR w KQkq - 1 2"
+        );
+    }
+
+    #[test]
+    fn fen_rejects_malformed_input() {
+        assert_eq!(
+            Board::from_fen("8/8/8/8/8/8/8 w - - 0 1").err(),
+            Some(FenError::InvalidPlacement(String::from("8/8/8/8/8/8/8")))
+        );
+        assert_eq!(
+            Board::from_fen("8/8/8/8/8/8/8/8 x - - 0 1").err(),
+            Some(FenError::InvalidActiveColor(String::from("x")))
+        );
+        assert_eq!(
+            Board::from_fen("8/8/8/8/8/8/8/8 w - e4 0 1").err(),
+            Some(FenError::InvalidEnPassant(String::from("e4")))
+        );
+        assert_eq!(
+            Board::from_fen("8/8/8/8/8/8/8/8 w - e3 0 1").err(),
+            Some(FenError::InvalidEnPassant(String::from("e3")))
+        );
+        assert_eq!(
+            Board::from_fen("8/8/8/8/8/8/8/8 b - e6 0 1").err(),
+            Some(FenError::InvalidEnPassant(String::from("e6")))
+        );
+        assert_eq!(
+            Board::from_fen("8/8/8/8/8/8/8/8 w").err(),
+            Some(FenError::WrongFieldCount(2))
+        );
+    }
+
+    #[test]
+    fn san_disambiguates_only_when_needed() {
+        let board = board(
+            [
+                "    ♚   ",
+                "        ",
+                "        ",
+                "   ♜    ",
+                "        ",
+                "♖   ♘   ",
+                " ♘      ",
+                "♖   ♔   ",
+            ],
+            Color::White,
+        );
+        let knight_move = board.parse_pgn_move("Nbd1").unwrap();
+        assert_eq!(board.to_pgn(&knight_move), "Nbd1");
+        let knight_capture = board.parse_pgn_move("Nxd5").unwrap();
+        assert_eq!(board.to_pgn(&knight_capture), "Nxd5");
+        let rook_move = board.parse_pgn_move("R3a2").unwrap();
+        assert_eq!(board.to_pgn(&rook_move), "R3a2");
+        let rook_check = board.parse_pgn_move("Ra8").unwrap();
+        assert_eq!(board.to_pgn(&rook_check), "Ra8+");
+    }
+
+    #[test]
+    fn san_writes_captures_and_promotions() {
+        let board = board(
+            [
+                "  ♜ ♚   ",
+                " ♙      ",
+                "        ",
+                "        ",
+                "        ",
+                "        ",
+                "        ",
+                "    ♔   ",
+            ],
+            Color::White,
+        );
+        let capture = board.parse_pgn_move("bxc8=Q").unwrap();
+        assert_eq!(board.to_pgn(&capture), "bxc8=Q+");
+        let push = board.parse_pgn_move("b8=N").unwrap();
+        assert_eq!(board.to_pgn(&push), "b8=N");
+    }
+
+    #[test]
+    fn parsing_reports_errors_instead_of_panicking() {
+        let board = Board::default();
+        assert_eq!(
+            board.parse_pgn_move("e9"),
+            Err(Error::UnparseableMove(String::from("e9")))
+        );
+        assert_eq!(
+            board.parse_pgn_move("Ke2"),
+            Err(Error::IllegalMove(String::from("Ke2")))
+        );
+        assert_eq!(
+            board.parse_pgn_move("O-O"),
+            Err(Error::IllegalMove(String::from("O-O")))
+        );
+        assert_eq!(
+            Location::parse_pgn("i1"),
+            Err(Error::InvalidFile(String::from("i")))
+        );
+        assert_eq!(
+            Location::parse_pgn("a0"),
+            Err(Error::InvalidRank(String::from("0")))
+        );
+        assert_eq!(
+            pgn_to_kind("X"),
+            Err(Error::InvalidPieceKind(String::from("X")))
+        );
+        assert!(Board::from_repr(String::from("♔")).is_err());
+        assert!(piece_from_repr("x", Location { rank: 0, file: 0 }).is_err());
+    }
+
+    #[test]
+    fn undo_restores_previous_positions() {
+        let mut board = Board::from_fen("r3k2r/1P6/8/3pP3/8/8/8/R3K2R w KQkq d6 4 20").unwrap();
+        let start = board.to_fen();
+        let mut fens = vec![start.clone()];
+        for pgn in ["exd6", "O-O", "bxa8=Q", "Kg7", "O-O-O"].iter() {
+            board.apply_move(board.parse_pgn_move(pgn).unwrap());
+            fens.push(board.to_fen());
+        }
+        assert_eq!(board.history().len(), 5);
+
+        for fen in fens.iter().rev().skip(1) {
+            assert!(board.undo_move().is_some());
+            assert_eq!(&board.to_fen(), fen);
+        }
+        assert_eq!(board.undo_move(), None);
+
+        for fen in fens.iter().skip(1) {
+            assert!(board.redo_move().is_some());
+            assert_eq!(&board.to_fen(), fen);
+        }
+        assert_eq!(board.redo_move(), None);
+
+        board.undo_move();
+        board.apply_move(board.parse_pgn_move("Kd1").unwrap());
+        assert_eq!(board.redo_move(), None);
+    }
+
+    #[test]
+    fn legal_moves_in_place_leave_the_board_unchanged() {
+        let mut board = Board::from_fen("r3k2r/1P6/8/3pP3/8/8/8/R3K2R w KQkq d6 4 20").unwrap();
+        board.apply_move(board.parse_pgn_move("Kf1").unwrap());
+        board.undo_move();
+        let fen = board.to_fen();
+        let hash = board.hash();
+        assert_eq!(board.legal_moves_in_place(), board.legal_moves());
+        assert_eq!(board.to_fen(), fen);
+        assert_eq!(board.hash(), hash);
+        assert!(board.history().is_empty());
+        assert!(board.redo_move().is_some());
+    }
+
+    #[test]
+    fn hash_depends_only_on_the_position() {
+        let mut board = Board::default();
+        let start = board.hash();
+        for pgn in ["Nf3", "Nf6", "Ng1", "Ng8"].iter() {
+            board.apply_move(board.parse_pgn_move(pgn).unwrap());
+            assert_eq!(
+                board.hash(),
+                Board::from_fen(&board.to_fen()).unwrap().hash()
             );
-            let move_piece = candidate_pieces[0];
-            return Move::Simple(move_piece.location(), dest_loc);
         }
-        panic!("Could not parse move: {}", pgn);
+        assert_eq!(board.hash(), start);
+
+        let mut transposed = Board::default();
+        for pgn in ["e4", "e6", "d4"].iter() {
+            board.apply_move(board.parse_pgn_move(pgn).unwrap());
+        }
+        for pgn in ["d4", "e6", "e4"].iter() {
+            transposed.apply_move(transposed.parse_pgn_move(pgn).unwrap());
+        }
+        // Only the first order leaves an en-passant square, and no black pawn can use it
+        assert_ne!(board.en_passant, transposed.en_passant);
+        assert_eq!(board.hash(), transposed.hash());
+
+        board.undo_move();
+        assert_ne!(board.hash(), transposed.hash());
+
+        let white = Board::from_fen("4k3/8/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+        let black = Board::from_fen("4k3/8/8/8/8/8/8/4K3 b - - 0 1").unwrap();
+        assert_ne!(white.hash(), black.hash());
+        let rights = Board::from_fen("r3k3/8/8/8/8/8/8/4K3 b q - 0 1").unwrap();
+        let no_rights = Board::from_fen("r3k3/8/8/8/8/8/8/4K3 b - - 0 1").unwrap();
+        assert_ne!(rights.hash(), no_rights.hash());
+    }
+
+    #[test]
+    fn hash_is_kept_up_to_date_by_moves() {
+        let mut board = Board::from_fen("r3k2r/1P6/8/8/4p3/8/3P4/R3K2R w KQkq - 0 1").unwrap();
+        let mut hashes = vec![board.hash()];
+        for pgn in ["d4", "exd3", "O-O-O", "Rf8", "bxa8=Q+", "Ke7"].iter() {
+            board.apply_move(board.parse_pgn_move(pgn).unwrap());
+            assert_eq!(board.hash(), board.computed_hash(), "after {}", pgn);
+            hashes.push(board.hash());
+        }
+        while board.undo_move().is_some() {
+            hashes.pop();
+            assert_eq!(board.hash(), *hashes.last().unwrap());
+            assert_eq!(board.hash(), board.computed_hash());
+        }
+    }
+
+    #[test]
+    fn draws_by_repetition_and_move_rules() {
+        let mut board = Board::default();
+        let shuffle = ["Nf3", "Nf6", "Ng1", "Ng8"];
+        for pgn in shuffle.iter().chain(shuffle.iter()) {
+            assert_eq!(board.claimable_draw(), None);
+            board.apply_move(board.parse_pgn_move(pgn).unwrap());
+        }
+        assert_eq!(board.repetitions(), 3);
+        assert_eq!(
+            board.claimable_draw(),
+            Some(DrawReason::ThreefoldRepetition)
+        );
+        assert_eq!(board.status(), GameStatus::Ongoing);
+        for pgn in shuffle.iter().chain(shuffle.iter()) {
+            board.apply_move(board.parse_pgn_move(pgn).unwrap());
+        }
+        assert_eq!(
+            board.status(),
+            GameStatus::Draw(DrawReason::FivefoldRepetition)
+        );
+        board.apply_move(board.parse_pgn_move("e4").unwrap());
+        assert_eq!(board.repetitions(), 1);
+
+        let mut board = Board::from_fen("4k3/8/8/8/8/8/8/R3K3 w - - 99 80").unwrap();
+        assert_eq!(board.claimable_draw(), None);
+        board.apply_move(board.parse_pgn_move("Ra2").unwrap());
+        assert_eq!(board.claimable_draw(), Some(DrawReason::FiftyMoves));
+
+        let mut board = Board::from_fen("4k3/8/8/8/8/8/8/R3K3 w - - 149 80").unwrap();
+        board.apply_move(board.parse_pgn_move("Ra2").unwrap());
+        assert_eq!(
+            board.status(),
+            GameStatus::Draw(DrawReason::SeventyFiveMoves)
+        );
+        // Mate on the last move still counts
+        let mut board = Board::from_fen("4k3/R7/8/8/8/8/8/1R2K3 w - - 149 80").unwrap();
+        board.apply_move(board.parse_pgn_move("Rb8").unwrap());
+        assert_eq!(board.status(), GameStatus::Checkmate(Color::White));
+    }
+
+    #[test]
+    fn detects_insufficient_material() {
+        for (fen, insufficient) in [
+            ("4k3/8/8/8/8/8/8/4K3 w - - 0 1", true),
+            ("4k3/8/8/8/8/8/8/4KN2 w - - 0 1", true),
+            ("4kb2/8/8/8/8/8/8/4K3 w - - 0 1", true),
+            ("2b1kb2/8/8/8/8/8/8/2B1K3 w - - 0 1", false),
+            ("4kb2/8/8/8/8/8/8/2B1K3 w - - 0 1", true),
+            ("4k3/8/8/8/8/8/8/2B1KB2 w - - 0 1", false),
+            ("4k3/8/8/8/8/8/8/4KNN1 w - - 0 1", false),
+            ("4kn2/8/8/8/8/8/8/4KN2 w - - 0 1", false),
+            ("4k3/8/8/8/8/8/4P3/4K3 w - - 0 1", false),
+            ("4k3/8/8/8/8/8/8/R3K3 w - - 0 1", false),
+        ]
+        .iter()
+        {
+            let board = Board::from_fen(fen).unwrap();
+            assert_eq!(board.is_insufficient_material(), *insufficient, "{}", fen);
+        }
+
+        let mut board = Board::from_fen("4k3/8/8/8/8/8/3r4/4K3 w - - 0 1").unwrap();
+        board.apply_move(board.parse_pgn_move("Kxd2").unwrap());
+        assert_eq!(
+            board.status(),
+            GameStatus::Draw(DrawReason::InsufficientMaterial)
+        );
+    }
+
+    #[test]
+    fn coordinate_moves_round_trip() {
+        let board = Board::from_fen("r3k2r/1P6/8/3pP3/8/8/8/R3K2R w KQkq d6 0 1").unwrap();
+        for r#move in board.legal_moves() {
+            let text = r#move.to_coordinates();
+            assert_eq!(board.parse_coordinate_move(&text), Ok(r#move), "{}", text);
+        }
+        assert_eq!(
+            board.parse_coordinate_move("e1g1"),
+            Ok(Move::Castle(Color::White, CastlingSide::Kingside))
+        );
+        assert_eq!(
+            board
+                .parse_coordinate_move("b7a8n")
+                .map(|r#move| board.to_pgn(&r#move)),
+            Ok(String::from("bxa8=N"))
+        );
+        assert_eq!(
+            board
+                .parse_coordinate_move("e5d6")
+                .map(|r#move| board.to_pgn(&r#move)),
+            Ok(String::from("exd6"))
+        );
+        assert_eq!(
+            board.parse_coordinate_move("e2e4"),
+            Err(Error::IllegalMove(String::from("e2e4")))
+        );
+        assert_eq!(
+            board.parse_coordinate_move("Nf3"),
+            Err(Error::UnparseableMove(String::from("Nf3")))
+        );
+    }
+
+    #[test]
+    fn bitboard_moves_match_piece_moves() {
+        for fen in [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R b KQkq - 0 1",
+            "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+            "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3",
+        ]
+        .iter()
+        {
+            let board = Board::from_fen(fen).unwrap();
+            let mut piece_moves: Vec<Move> = vec![];
+            for piece in board.pieces() {
+                if piece.color() == board.to_move {
+                    piece_moves.append(&mut piece.possible_moves(&board));
+                }
+            }
+            let bitboard_moves = board.possible_moves();
+            assert_eq!(bitboard_moves.len(), piece_moves.len(), "{}", fen);
+            for r#move in piece_moves {
+                assert!(bitboard_moves.contains(&r#move), "{:?} in {}", r#move, fen);
+            }
+        }
+    }
+
+    fn king_moves(board: &Board) -> Vec<Move> {
+        for piece in board.pieces.values() {
+            if piece.color() == board.to_move && piece.kind() == Kind::King {
+                return piece.possible_moves(board);
+            }
+        }
+        panic!("No king to move");
+    }
+
+    fn castles(moves: Vec<Move>) -> Vec<&'static str> {
+        let mut result: Vec<&str> = moves
+            .iter()
+            .filter_map(|r#move| match r#move {
+                Move::Castle(_, CastlingSide::Kingside) => Some("O-O"),
+                Move::Castle(_, CastlingSide::Queenside) => Some("O-O-O"),
+                _ => None,
+            })
+            .collect();
+        result.sort();
+        return result;
+    }
+
+    fn destinations(moves: &[Move], from: &str) -> Vec<String> {
+        let from = Location::parse_pgn(from).unwrap();
+        let mut result: Vec<String> = moves
+            .iter()
+            .filter_map(|r#move| match r#move {
+                Move::Simple(source, to) if *source == from => Some(to.pgn()),
+                _ => None,
+            })
+            .collect();
+        result.sort();
+        return result;
+    }
+
+    const CASTLING: [&str; 8] = [
+        "♜   ♚  ♜",
+        "        ",
+        "        ",
+        "        ",
+        "        ",
+        "        ",
+        "        ",
+        "♖   ♔  ♖",
+    ];
+
+    #[test]
+    fn kings_step_to_adjacent_squares_and_castle() {
+        let board = board(CASTLING, Color::White);
+        let moves = king_moves(&board);
+        assert_eq!(moves.len(), 7);
+        assert_eq!(castles(moves), vec!["O-O", "O-O-O"]);
+    }
+
+    #[test]
+    fn castling_moves_the_king_and_the_rook() {
+        let mut board = board(CASTLING, Color::White);
+        board.apply_move(board.parse_pgn_move("O-O").unwrap());
+        assert!(board.has_piece(
+            &Location::parse_pgn("g1").unwrap(),
+            Color::White,
+            Kind::King
+        ));
+        assert!(board.has_piece(
+            &Location::parse_pgn("f1").unwrap(),
+            Color::White,
+            Kind::Rook
+        ));
+        board.apply_move(board.parse_pgn_move("O-O-O").unwrap());
+        assert!(board.has_piece(
+            &Location::parse_pgn("c8").unwrap(),
+            Color::Black,
+            Kind::King
+        ));
+        assert!(board.has_piece(
+            &Location::parse_pgn("d8").unwrap(),
+            Color::Black,
+            Kind::Rook
+        ));
+    }
+
+    #[test]
+    fn cannot_castle_through_pieces() {
+        let board = board(
+            [
+                "♜   ♚  ♜",
+                "        ",
+                "        ",
+                "        ",
+                "        ",
+                "        ",
+                "        ",
+                "♖♘  ♔ ♘♖",
+            ],
+            Color::White,
+        );
+        assert!(castles(king_moves(&board)).is_empty());
+    }
+
+    #[test]
+    fn castling_rights_are_lost_when_the_king_or_a_rook_moves() {
+        let mut board = board(CASTLING, Color::White);
+        board.apply_move(board.parse_pgn_move("Rh2").unwrap());
+        board.apply_move(board.parse_pgn_move("Ke7").unwrap());
+        board.apply_move(board.parse_pgn_move("Rh1").unwrap());
+        board.apply_move(board.parse_pgn_move("Ke8").unwrap());
+        // Moving the pieces back does not restore the rights
+        assert_eq!(castles(king_moves(&board)), vec!["O-O-O"]);
+        board.apply_move(board.parse_pgn_move("Ra2").unwrap());
+        assert!(castles(king_moves(&board)).is_empty());
+    }
+
+    #[test]
+    fn capturing_a_rook_removes_its_castling_right() {
+        let mut board = board(
+            [
+                "♜   ♚  ♜",
+                "        ",
+                "        ",
+                "        ",
+                "        ",
+                "        ",
+                " ♗      ",
+                "    ♔   ",
+            ],
+            Color::White,
+        );
+        board.apply_move(board.parse_pgn_move("Bxh8").unwrap());
+        assert_eq!(castles(king_moves(&board)), vec!["O-O-O"]);
+    }
+
+    #[test]
+    fn pinned_pieces_may_only_move_along_the_pin() {
+        let pinned = |piece: &str| {
+            let row = format!("    {}   ", piece);
+            return board(
+                [
+                    "    ♚   ",
+                    "    ♜   ",
+                    "        ",
+                    "        ",
+                    "        ",
+                    "        ",
+                    &row,
+                    "    ♔   ",
+                ],
+                Color::White,
+            )
+            .legal_moves();
+        };
+        assert!(destinations(&pinned("♘"), "e2").is_empty());
+        assert_eq!(
+            destinations(&pinned("♖"), "e2"),
+            vec!["e3", "e4", "e5", "e6", "e7"]
+        );
+    }
+
+    #[test]
+    fn kings_cannot_step_into_check() {
+        let board = board(
+            [
+                "   ♜♚   ",
+                "        ",
+                "        ",
+                "        ",
+                "        ",
+                "        ",
+                "        ",
+                "    ♔   ",
+            ],
+            Color::White,
+        );
+        assert_eq!(
+            destinations(&board.legal_moves(), "e1"),
+            vec!["e2", "f1", "f2"]
+        );
+    }
+
+    #[test]
+    fn cannot_castle_out_of_through_or_into_check() {
+        let castles_with_rook_on = |row: &str| {
+            return castles(
+                board(
+                    [
+                        "    ♚   ",
+                        row,
+                        "        ",
+                        "        ",
+                        "        ",
+                        "        ",
+                        "        ",
+                        "♖   ♔  ♖",
+                    ],
+                    Color::White,
+                )
+                .legal_moves(),
+            );
+        };
+        assert!(castles_with_rook_on("    ♜   ").is_empty());
+        assert_eq!(castles_with_rook_on("     ♜  "), vec!["O-O-O"]);
+        assert_eq!(castles_with_rook_on("  ♜     "), vec!["O-O"]);
+        // Only the squares the king crosses matter, not the one the rook crosses
+        assert_eq!(castles_with_rook_on(" ♜      "), vec!["O-O", "O-O-O"]);
+    }
+
+    #[test]
+    fn reports_check_checkmate_and_stalemate() {
+        assert_eq!(Board::default().status(), GameStatus::Ongoing);
+
+        let mut mate = board(
+            [
+                "      ♚ ",
+                "     ♟♟♟",
+                "        ",
+                "        ",
+                "        ",
+                "        ",
+                "        ",
+                "♖     ♔ ",
+            ],
+            Color::White,
+        );
+        let r#move = mate.parse_pgn_move("Ra8").unwrap();
+        assert_eq!(mate.to_pgn(&r#move), "Ra8#");
+        mate.apply_move(r#move);
+        assert_eq!(mate.status(), GameStatus::Checkmate(Color::White));
+        assert!(mate.legal_moves().is_empty());
+
+        let check = board(
+            [
+                "♖     ♚ ",
+                "        ",
+                "        ",
+                "        ",
+                "        ",
+                "        ",
+                "        ",
+                "      ♔ ",
+            ],
+            Color::Black,
+        );
+        assert_eq!(check.status(), GameStatus::Check);
+        assert_eq!(
+            destinations(&check.legal_moves(), "g8"),
+            vec!["f7", "g7", "h7"]
+        );
+
+        let stalemate = board(
+            [
+                "♚       ",
+                "        ",
+                " ♕      ",
+                "        ",
+                "        ",
+                "        ",
+                "        ",
+                "       ♔",
+            ],
+            Color::Black,
+        );
+        assert_eq!(stalemate.status(), GameStatus::Stalemate);
+    }
+
+    #[test]
+    fn en_passant_removes_the_passed_pawn() {
+        let mut board = board(
+            [
+                "    ♚   ",
+                "   ♟    ",
+                "        ",
+                "    ♙   ",
+                "        ",
+                "        ",
+                "        ",
+                "    ♔   ",
+            ],
+            Color::Black,
+        );
+        board.apply_move(board.parse_pgn_move("d5").unwrap());
+        let r#move = board.parse_pgn_move("exd6").unwrap();
+        assert_eq!(
+            r#move,
+            Move::EnPassant(
+                Location::parse_pgn("e5").unwrap(),
+                Location::parse_pgn("d6").unwrap()
+            )
+        );
+        board.apply_move(r#move);
+        assert!(board
+            .get_piece(&Location::parse_pgn("d5").unwrap())
+            .is_none());
+        assert!(board.has_piece(
+            &Location::parse_pgn("d6").unwrap(),
+            Color::White,
+            Kind::Pawn
+        ));
+        assert_eq!(board.undo_move(), Some(r#move));
+        assert!(board.has_piece(
+            &Location::parse_pgn("d5").unwrap(),
+            Color::Black,
+            Kind::Pawn
+        ));
+        assert!(board
+            .get_piece(&Location::parse_pgn("d6").unwrap())
+            .is_none());
+    }
+
+    #[test]
+    fn promotion_places_the_chosen_piece() {
+        let rows = [
+            "♜   ♚   ",
+            " ♙      ",
+            "        ",
+            "        ",
+            "        ",
+            "        ",
+            "        ",
+            "    ♔   ",
+        ];
+        let mut knight = board(rows, Color::White);
+        knight.apply_move(knight.parse_pgn_move("b8=N").unwrap());
+        assert!(knight
+            .get_piece(&Location::parse_pgn("b7").unwrap())
+            .is_none());
+        assert!(knight.has_piece(
+            &Location::parse_pgn("b8").unwrap(),
+            Color::White,
+            Kind::Knight
+        ));
+
+        let mut queen = board(rows, Color::White);
+        queen.apply_move(queen.parse_pgn_move("bxa8=Q").unwrap());
+        assert!(queen
+            .get_piece(&Location::parse_pgn("b7").unwrap())
+            .is_none());
+        assert!(queen.has_piece(
+            &Location::parse_pgn("a8").unwrap(),
+            Color::White,
+            Kind::Queen
+        ));
     }
 }
@@ -0,0 +1,40 @@
+use crate::board::FenError;
+
+#[derive(Debug, Eq, PartialEq)]
+pub enum Error {
+    InvalidRank(String),
+    InvalidFile(String),
+    InvalidSquare(String),
+    InvalidPieceKind(String),
+    InvalidRepr(String),
+    /// The input is not a move in any notation we understand.
+    UnparseableMove(String),
+    /// The input is well-formed but no legal move matches it.
+    IllegalMove(String),
+    AmbiguousMove(String),
+    Fen(FenError),
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        return match self {
+            Error::InvalidRank(rank) => write!(f, "Invalid rank: {}", rank),
+            Error::InvalidFile(file) => write!(f, "Invalid file: {}", file),
+            Error::InvalidSquare(square) => write!(f, "Invalid square: {}", square),
+            Error::InvalidPieceKind(kind) => write!(f, "Invalid piece kind: {}", kind),
+            Error::InvalidRepr(repr) => write!(f, "Invalid board repr: {}", repr),
+            Error::UnparseableMove(pgn) => write!(f, "Could not parse move: {}", pgn),
+            Error::IllegalMove(pgn) => write!(f, "No pieces can make the move: {}", pgn),
+            Error::AmbiguousMove(pgn) => write!(f, "Move is ambiguous: {}", pgn),
+            Error::Fen(error) => write!(f, "{}", error),
+        };
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<FenError> for Error {
+    fn from(error: FenError) -> Self {
+        return Error::Fen(error);
+    }
+}
@@ -0,0 +1,467 @@
+use crate::bitboard::{self, Bitboard};
+use crate::board::{Board, Color, Kind, Location};
+
+/// The game phase with all the minor and major pieces still on the board; it falls to zero as
+/// they come off, moving the evaluation from its middlegame to its endgame values.
+pub const MAX_PHASE: i32 = 24;
+
+/// A middlegame and an endgame value, blended by game phase.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct Score {
+    pub middlegame: i32,
+    pub endgame: i32,
+}
+
+impl Score {
+    pub const fn new(middlegame: i32, endgame: i32) -> Self {
+        return Score {
+            middlegame: middlegame,
+            endgame: endgame,
+        };
+    }
+
+    pub fn tapered(&self, phase: i32) -> i32 {
+        return (self.middlegame * phase + self.endgame * (MAX_PHASE - phase)) / MAX_PHASE;
+    }
+
+    fn add(&mut self, other: Score) {
+        self.middlegame += other.middlegame;
+        self.endgame += other.endgame;
+    }
+
+    fn sub(&mut self, other: Score) {
+        self.middlegame -= other.middlegame;
+        self.endgame -= other.endgame;
+    }
+}
+
+/// Every evaluation term, each as White's score minus Black's, for finding out why a position
+/// is scored the way it is.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct Evaluation {
+    pub material: Score,
+    pub piece_squares: Score,
+    pub pawn_structure: Score,
+    pub mobility: Score,
+    pub king_safety: Score,
+    /// From `MAX_PHASE` in the opening down to 0 with only kings and pawns left.
+    pub phase: i32,
+}
+
+impl Evaluation {
+    fn terms(&self) -> [(&'static str, Score); 5] {
+        return [
+            ("Material", self.material),
+            ("Piece squares", self.piece_squares),
+            ("Pawn structure", self.pawn_structure),
+            ("Mobility", self.mobility),
+            ("King safety", self.king_safety),
+        ];
+    }
+
+    /// The blended score in centipawns, positive when White is better.
+    pub fn total(&self) -> i32 {
+        return self
+            .terms()
+            .iter()
+            .map(|(_, score)| score.tapered(self.phase))
+            .sum();
+    }
+}
+
+impl std::fmt::Display for Evaluation {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        writeln!(f, "{:<16}{:>8}{:>8}{:>8}", "Term", "MG", "EG", "Total")?;
+        for (name, score) in self.terms().iter() {
+            writeln!(
+                f,
+                "{:<16}{:>8}{:>8}{:>8}",
+                name,
+                score.middlegame,
+                score.endgame,
+                score.tapered(self.phase)
+            )?;
+        }
+        writeln!(f, "Phase {}/{}", self.phase, MAX_PHASE)?;
+        return write!(f, "Total {} (White's view)", self.total());
+    }
+}
+
+fn material_value(kind: Kind) -> Score {
+    return match kind {
+        Kind::Pawn => Score::new(82, 94),
+        Kind::Knight => Score::new(337, 281),
+        Kind::Bishop => Score::new(365, 297),
+        Kind::Rook => Score::new(477, 512),
+        Kind::Queen => Score::new(1025, 936),
+        Kind::King => Score::new(0, 0),
+    };
+}
+
+fn phase_weight(kind: Kind) -> i32 {
+    return match kind {
+        Kind::Knight | Kind::Bishop => 1,
+        Kind::Rook => 2,
+        Kind::Queen => 4,
+        Kind::Pawn | Kind::King => 0,
+    };
+}
+
+// Piece-square tables from White's side, laid out as the board is printed: a8 first, h1 last.
+#[rustfmt::skip]
+const PAWN_MIDDLEGAME: [i32; 64] = [
+     0,  0,  0,  0,  0,  0,  0,  0,
+    50, 50, 50, 50, 50, 50, 50, 50,
+    10, 10, 20, 30, 30, 20, 10, 10,
+     5,  5, 10, 25, 25, 10,  5,  5,
+     0,  0,  0, 20, 20,  0,  0,  0,
+     5, -5,-10,  0,  0,-10, -5,  5,
+     5, 10, 10,-20,-20, 10, 10,  5,
+     0,  0,  0,  0,  0,  0,  0,  0,
+];
+
+#[rustfmt::skip]
+const PAWN_ENDGAME: [i32; 64] = [
+     0,  0,  0,  0,  0,  0,  0,  0,
+    80, 80, 80, 80, 80, 80, 80, 80,
+    50, 50, 50, 50, 50, 50, 50, 50,
+    30, 30, 30, 30, 30, 30, 30, 30,
+    20, 20, 20, 20, 20, 20, 20, 20,
+    10, 10, 10, 10, 10, 10, 10, 10,
+     5,  5,  5,  5,  5,  5,  5,  5,
+     0,  0,  0,  0,  0,  0,  0,  0,
+];
+
+#[rustfmt::skip]
+const KNIGHT_SQUARES: [i32; 64] = [
+    -50,-40,-30,-30,-30,-30,-40,-50,
+    -40,-20,  0,  0,  0,  0,-20,-40,
+    -30,  0, 10, 15, 15, 10,  0,-30,
+    -30,  5, 15, 20, 20, 15,  5,-30,
+    -30,  0, 15, 20, 20, 15,  0,-30,
+    -30,  5, 10, 15, 15, 10,  5,-30,
+    -40,-20,  0,  5,  5,  0,-20,-40,
+    -50,-40,-30,-30,-30,-30,-40,-50,
+];
+
+#[rustfmt::skip]
+const BISHOP_SQUARES: [i32; 64] = [
+    -20,-10,-10,-10,-10,-10,-10,-20,
+    -10,  0,  0,  0,  0,  0,  0,-10,
+    -10,  0,  5, 10, 10,  5,  0,-10,
+    -10,  5,  5, 10, 10,  5,  5,-10,
+    -10,  0, 10, 10, 10, 10,  0,-10,
+    -10, 10, 10, 10, 10, 10, 10,-10,
+    -10,  5,  0,  0,  0,  0,  5,-10,
+    -20,-10,-10,-10,-10,-10,-10,-20,
+];
+
+#[rustfmt::skip]
+const ROOK_SQUARES: [i32; 64] = [
+     0,  0,  0,  0,  0,  0,  0,  0,
+     5, 10, 10, 10, 10, 10, 10,  5,
+    -5,  0,  0,  0,  0,  0,  0, -5,
+    -5,  0,  0,  0,  0,  0,  0, -5,
+    -5,  0,  0,  0,  0,  0,  0, -5,
+    -5,  0,  0,  0,  0,  0,  0, -5,
+    -5,  0,  0,  0,  0,  0,  0, -5,
+     0,  0,  0,  5,  5,  0,  0,  0,
+];
+
+#[rustfmt::skip]
+const QUEEN_SQUARES: [i32; 64] = [
+    -20,-10,-10, -5, -5,-10,-10,-20,
+    -10,  0,  0,  0,  0,  0,  0,-10,
+    -10,  0,  5,  5,  5,  5,  0,-10,
+     -5,  0,  5,  5,  5,  5,  0, -5,
+      0,  0,  5,  5,  5,  5,  0, -5,
+    -10,  5,  5,  5,  5,  5,  0,-10,
+    -10,  0,  5,  0,  0,  0,  0,-10,
+    -20,-10,-10, -5, -5,-10,-10,-20,
+];
+
+#[rustfmt::skip]
+const KING_MIDDLEGAME: [i32; 64] = [
+    -30,-40,-40,-50,-50,-40,-40,-30,
+    -30,-40,-40,-50,-50,-40,-40,-30,
+    -30,-40,-40,-50,-50,-40,-40,-30,
+    -30,-40,-40,-50,-50,-40,-40,-30,
+    -20,-30,-30,-40,-40,-30,-30,-20,
+    -10,-20,-20,-20,-20,-20,-20,-10,
+     20, 20,  0,  0,  0,  0, 20, 20,
+     20, 30, 10,  0,  0, 10, 30, 20,
+];
+
+#[rustfmt::skip]
+const KING_ENDGAME: [i32; 64] = [
+    -50,-40,-30,-20,-20,-30,-40,-50,
+    -30,-20,-10,  0,  0,-10,-20,-30,
+    -30,-10, 20, 30, 30, 20,-10,-30,
+    -30,-10, 30, 40, 40, 30,-10,-30,
+    -30,-10, 30, 40, 40, 30,-10,-30,
+    -30,-10, 20, 30, 30, 20,-10,-30,
+    -30,-30,  0,  0,  0,  0,-30,-30,
+    -50,-30,-30,-30,-30,-30,-30,-50,
+];
+
+fn piece_square(kind: Kind, color: Color, location: &Location) -> Score {
+    let rank = match color {
+        Color::White => 7 - location.rank,
+        Color::Black => location.rank,
+    };
+    let index = (rank * 8 + location.file) as usize;
+    let (middlegame, endgame) = match kind {
+        Kind::Pawn => (&PAWN_MIDDLEGAME, &PAWN_ENDGAME),
+        Kind::Knight => (&KNIGHT_SQUARES, &KNIGHT_SQUARES),
+        Kind::Bishop => (&BISHOP_SQUARES, &BISHOP_SQUARES),
+        Kind::Rook => (&ROOK_SQUARES, &ROOK_SQUARES),
+        Kind::Queen => (&QUEEN_SQUARES, &QUEEN_SQUARES),
+        Kind::King => (&KING_MIDDLEGAME, &KING_ENDGAME),
+    };
+    return Score::new(middlegame[index], endgame[index]);
+}
+
+const FILE_A: u64 = 0x0101_0101_0101_0101;
+
+fn file_mask(file: i8) -> u64 {
+    if !(0..8).contains(&file) {
+        return 0;
+    }
+    return FILE_A << file;
+}
+
+/// The ranks strictly in front of `rank` from `color`'s side of the board.
+fn ranks_ahead(color: Color, rank: u8) -> u64 {
+    return match color {
+        Color::White if rank == 7 => 0,
+        Color::White => !0 << (8 * (rank + 1)),
+        Color::Black => (1 << (8 * rank)) - 1,
+    };
+}
+
+const DOUBLED_PAWN: Score = Score::new(-10, -20);
+const ISOLATED_PAWN: Score = Score::new(-15, -20);
+/// By how many ranks the pawn has advanced.
+const PASSED_PAWN: [Score; 8] = [
+    Score::new(0, 0),
+    Score::new(5, 10),
+    Score::new(10, 20),
+    Score::new(20, 40),
+    Score::new(35, 70),
+    Score::new(60, 120),
+    Score::new(100, 200),
+    Score::new(0, 0),
+];
+
+fn pawn_structure(board: &Board, color: Color) -> Score {
+    let own = board.bitboards().pieces(color, Kind::Pawn).0;
+    let enemy = board.bitboards().pieces(color.opponent(), Kind::Pawn).0;
+    let mut result = Score::default();
+    for file in 0..8 {
+        let on_file = (own & file_mask(file)).count_ones() as i32;
+        for _ in 1..on_file {
+            result.add(DOUBLED_PAWN);
+        }
+    }
+    for pawn in Bitboard(own).squares() {
+        let file = pawn.file as i8;
+        let neighbours = file_mask(file - 1) | file_mask(file + 1);
+        if own & neighbours == 0 {
+            result.add(ISOLATED_PAWN);
+        }
+        if enemy & (neighbours | file_mask(file)) & ranks_ahead(color, pawn.rank) == 0 {
+            let advanced = match color {
+                Color::White => pawn.rank,
+                Color::Black => 7 - pawn.rank,
+            };
+            result.add(PASSED_PAWN[advanced as usize]);
+        }
+    }
+    return result;
+}
+
+fn mobility_weight(kind: Kind) -> Score {
+    return match kind {
+        Kind::Knight => Score::new(4, 4),
+        Kind::Bishop => Score::new(5, 5),
+        Kind::Rook => Score::new(2, 4),
+        Kind::Queen => Score::new(1, 2),
+        Kind::Pawn | Kind::King => Score::new(0, 0),
+    };
+}
+
+const PAWN_SHIELD: i32 = 10;
+const OPEN_KING_FILE: i32 = -20;
+const KING_ZONE_ATTACK: i32 = -8;
+
+/// Pawns sheltering the king, missing pawns on its file, and enemy attacks around it. This only
+/// matters while there are pieces left to attack with, so it has no endgame value.
+fn king_safety(board: &Board, color: Color) -> Score {
+    let pieces = board.bitboards();
+    let king = match board.king_location(color) {
+        Some(king) => king,
+        None => return Score::default(),
+    };
+    let own_pawns = pieces.pieces(color, Kind::Pawn);
+    let mut result = 0;
+
+    let shield_ranks: u64 = match color {
+        Color::White if king.rank < 6 => 0xffff << (8 * (king.rank + 1)),
+        Color::Black if king.rank > 1 => 0xffff << (8 * (king.rank - 2)),
+        _ => 0,
+    };
+    let king_file = king.file as i8;
+    let shield_files = file_mask(king_file - 1) | file_mask(king_file) | file_mask(king_file + 1);
+    result += PAWN_SHIELD * (own_pawns.0 & shield_ranks & shield_files).count_ones() as i32;
+    if own_pawns.0 & file_mask(king_file) == 0 {
+        result += OPEN_KING_FILE;
+    }
+
+    let zone = bitboard::king_attacks(&king) | Bitboard::from_location(&king);
+    let occupied = pieces.occupied();
+    let enemy = color.opponent();
+    for kind in [Kind::Knight, Kind::Bishop, Kind::Rook, Kind::Queen].iter() {
+        for from in pieces.pieces(enemy, *kind).squares() {
+            let attacks = match kind {
+                Kind::Knight => bitboard::knight_attacks(&from),
+                Kind::Bishop => bitboard::bishop_attacks(&from, occupied),
+                Kind::Rook => bitboard::rook_attacks(&from, occupied),
+                _ => bitboard::queen_attacks(&from, occupied),
+            };
+            result += KING_ZONE_ATTACK * (attacks & zone).count() as i32;
+        }
+    }
+    return Score::new(result, 0);
+}
+
+/// Scores every term of the evaluation separately; see `evaluate` for the combined score.
+pub fn breakdown(board: &Board) -> Evaluation {
+    let mut result = Evaluation::default();
+    for piece in board.pieces() {
+        let kind = piece.kind();
+        let color = piece.color();
+        let location = piece.location();
+        let mut material = material_value(kind);
+        let mut piece_squares = piece_square(kind, color, &location);
+        let weight = mobility_weight(kind);
+        let moves = match kind {
+            Kind::Pawn | Kind::King => 0,
+            _ => piece.possible_moves(board).len() as i32,
+        };
+        let mut mobility = Score::new(weight.middlegame * moves, weight.endgame * moves);
+        if color == Color::Black {
+            material = Score::new(-material.middlegame, -material.endgame);
+            piece_squares = Score::new(-piece_squares.middlegame, -piece_squares.endgame);
+            mobility = Score::new(-mobility.middlegame, -mobility.endgame);
+        }
+        result.material.add(material);
+        result.piece_squares.add(piece_squares);
+        result.mobility.add(mobility);
+        result.phase += phase_weight(kind);
+    }
+    result.phase = result.phase.min(MAX_PHASE);
+
+    result.pawn_structure = pawn_structure(board, Color::White);
+    result
+        .pawn_structure
+        .sub(pawn_structure(board, Color::Black));
+    result.king_safety = king_safety(board, Color::White);
+    result.king_safety.sub(king_safety(board, Color::Black));
+    return result;
+}
+
+/// The static value of `board` in centipawns, from the point of view of the side to move.
+pub fn evaluate(board: &Board) -> i32 {
+    let total = breakdown(board).total();
+    return match board.to_move {
+        Color::White => total,
+        Color::Black => -total,
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The same position with the colours swapped and the board flipped top to bottom.
+    fn mirror(fen: &str) -> String {
+        let fields: Vec<&str> = fen.split(' ').collect();
+        let swap_case = |text: &str| -> String {
+            return text
+                .chars()
+                .map(|c| {
+                    if c.is_ascii_uppercase() {
+                        return c.to_ascii_lowercase();
+                    }
+                    return c.to_ascii_uppercase();
+                })
+                .collect();
+        };
+        let placement: Vec<String> = fields[0].split('/').rev().map(swap_case).collect();
+        let to_move = match fields[1] {
+            "w" => "b",
+            _ => "w",
+        };
+        return format!("{} {} - - 0 1", placement.join("/"), to_move);
+    }
+
+    #[test]
+    fn symmetric_positions_are_even() {
+        assert_eq!(evaluate(&Board::default()), 0);
+        for fen in [
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w - - 0 1",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+            "r4rk1/1pp1qppp/p1np1n2/2b1p1B1/2B1P1b1/P1NP1N2/1PP1QPPP/R4RK1 b - - 0 10",
+        ]
+        .iter()
+        {
+            let board = Board::from_fen(fen).unwrap();
+            let mirrored = Board::from_fen(&mirror(fen)).unwrap();
+            assert_eq!(evaluate(&board), evaluate(&mirrored), "{}", fen);
+            assert_eq!(breakdown(&board).total(), -breakdown(&mirrored).total());
+        }
+    }
+
+    #[test]
+    fn extra_material_wins() {
+        let board = Board::from_fen("4k3/8/8/8/8/8/8/3QK3 w - - 0 1").unwrap();
+        assert!(evaluate(&board) > 800);
+        let board = Board::from_fen("4k3/8/8/8/8/8/8/3QK3 b - - 0 1").unwrap();
+        assert!(evaluate(&board) < -800);
+    }
+
+    #[test]
+    fn scores_pawn_structure() {
+        let board = Board::from_fen("4k3/8/8/8/8/2P5/2P5/4K3 w - - 0 1").unwrap();
+        let doubled_and_isolated = breakdown(&board).pawn_structure;
+        let board = Board::from_fen("4k3/8/8/8/8/8/2PP4/4K3 w - - 0 1").unwrap();
+        let connected = breakdown(&board).pawn_structure;
+        assert!(doubled_and_isolated.endgame < connected.endgame);
+
+        let passed = Board::from_fen("4k3/8/1P6/8/8/8/8/4K3 w - - 0 1").unwrap();
+        let blocked = Board::from_fen("4k3/p7/1P6/8/8/8/8/4K3 w - - 0 1").unwrap();
+        assert!(
+            breakdown(&passed).pawn_structure.endgame
+                > breakdown(&blocked).pawn_structure.endgame + 50
+        );
+    }
+
+    #[test]
+    fn developed_pieces_are_more_mobile() {
+        let mut board = Board::default();
+        board.apply_move(board.parse_pgn_move("e4").unwrap());
+        assert!(breakdown(&board).mobility.middlegame > 0);
+    }
+
+    #[test]
+    fn breakdown_adds_up() {
+        let board = Board::from_fen(
+            "r4rk1/1pp1qppp/p1np1n2/2b1p1B1/2B1P1b1/P1NP1N2/1PP1QPPP/R4RK1 w - - 0 10",
+        )
+        .unwrap();
+        let evaluation = breakdown(&board);
+        assert_eq!(evaluation.phase, MAX_PHASE);
+        assert_eq!(evaluate(&board), evaluation.total());
+        assert!(evaluation.to_string().contains("King safety"));
+    }
+}
@@ -0,0 +1,28 @@
+#![allow(clippy::needless_return, clippy::redundant_field_names)]
+
+mod bitboard;
+mod board;
+mod error;
+mod evaluate;
+mod ordering;
+mod pgn;
+mod search;
+mod see;
+#[cfg(test)]
+mod test_output;
+mod transposition;
+mod uci;
+mod xboard;
+mod zobrist;
+
+pub use board::{
+    Bishop, Board, CastlingRights, CastlingSide, Color, Diff, DrawReason, FenError, GameStatus,
+    Kind, King, Knight, Location, Move, Pawn, Piece, Queen, Rook,
+};
+pub use error::Error;
+pub use evaluate::{breakdown, evaluate, Evaluation, Score};
+pub use pgn::{Game, GameMove, PgnError, PgnReader};
+pub use search::{search, Progress, SearchLimits, SearchResult, Searcher};
+pub use transposition::{TableStats, TranspositionTable};
+pub use uci::Uci;
+pub use xboard::XBoard;
@@ -1,16 +1,120 @@
-mod board;
-
-use text_io::read;
+use chess::{Board, GameStatus};
+use std::io::BufRead;
 
 fn main() {
-    let mut board = board::Board::default();
+    let args: Vec<String> = std::env::args().collect();
+    match args.get(1).map(|arg| arg.as_str()) {
+        Some("perft") => perft(&args[2..]),
+        Some("uci") => chess::Uci::new(std::io::stdout()).run(std::io::stdin().lock()),
+        Some("xboard") => chess::XBoard::new(std::io::stdout()).run(std::io::stdin().lock()),
+        Some(command) => {
+            eprintln!("Unknown command: {}", command);
+            eprintln!("Usage: chess [perft <fen> <depth> | uci | xboard]");
+            std::process::exit(2);
+        }
+        None => play(),
+    }
+}
+
+/// Prints the node count below each legal move, then the total, like `divide` in other engines.
+fn perft(args: &[String]) {
+    // Accept the FEN either quoted as one argument or split on its spaces
+    let (depth, fen) = match args.split_last() {
+        Some((depth, fen)) if !fen.is_empty() => (depth, fen.join(" ")),
+        _ => {
+            eprintln!("Usage: chess perft <fen> <depth>");
+            std::process::exit(2);
+        }
+    };
+    let depth: u32 = match depth.parse() {
+        Ok(depth) => depth,
+        Err(_) => {
+            eprintln!("Invalid depth: {}", depth);
+            std::process::exit(2);
+        }
+    };
+    let board = match Board::from_fen(&fen) {
+        Ok(board) => board,
+        Err(error) => {
+            eprintln!("{}", error);
+            std::process::exit(2);
+        }
+    };
+
+    // Depth 0 counts the position itself, with no moves to list
+    let mut total = if depth == 0 { 1 } else { 0 };
+    for (r#move, nodes) in board.divide(depth) {
+        println!("{}: {}", board.to_pgn(&r#move), nodes);
+        total += nodes;
+    }
+    println!();
+    println!("Nodes searched: {}", total);
+}
+
+fn play() {
+    let mut board = Board::default();
     print!("{}", board.to_str());
 
-    loop {
-        let line: String = read!("{}\n");
-        let r#move = board.parse_pgn_move(line.trim());
+    let stdin = std::io::stdin();
+    for line in stdin.lock().lines() {
+        let line = line.expect("Could not read from stdin");
+        let command = line.trim();
+        if command == "undo" || command == "redo" {
+            let r#move = match command {
+                "undo" => board.undo_move(),
+                _ => board.redo_move(),
+            };
+            match r#move {
+                Some(r#move) => {
+                    println!("{:?}", r#move);
+                    print!("{}", board.to_str());
+                }
+                None => println!("Nothing to {}.", command),
+            }
+            continue;
+        }
+        if command == "draw" {
+            match board.claimable_draw() {
+                Some(reason) => {
+                    println!("Draw claimed by {}. The game is drawn.", reason);
+                    break;
+                }
+                None => println!("No draw can be claimed."),
+            }
+            continue;
+        }
+        let r#move = match board.parse_pgn_move(line.trim()) {
+            Ok(r#move) => r#move,
+            Err(error) => {
+                println!("{}. Please try again.", error);
+                continue;
+            }
+        };
         println!("{:?}", r#move);
         board.apply_move(r#move);
         print!("{}", board.to_str());
+
+        match board.status() {
+            GameStatus::Checkmate(winner) => {
+                println!("Checkmate! {:?} wins.", winner);
+                break;
+            }
+            GameStatus::Stalemate => {
+                println!("Stalemate! The game is drawn.");
+                break;
+            }
+            GameStatus::Draw(reason) => {
+                println!("The game is drawn by {}.", reason);
+                break;
+            }
+            GameStatus::Check => println!("Check!"),
+            GameStatus::Ongoing => {}
+        }
+        if let Some(reason) = board.claimable_draw() {
+            println!(
+                "A draw can be claimed by {}; enter \"draw\" to claim it.",
+                reason
+            );
+        }
     }
 }
@@ -0,0 +1,213 @@
+use crate::bitboard;
+use crate::board::{Board, Color, Kind, Location, Move};
+use crate::search::MAX_PLY;
+use crate::see::exchange_value;
+
+const HASH_MOVE: i32 = 1_000_000;
+const CAPTURE: i32 = 100_000;
+const PROMOTION: i32 = 90_000;
+const KILLERS: [i32; 2] = [80_000, 79_000];
+/// History scores are halved once any of them reaches this, so they always stay below killers.
+const HISTORY_LIMIT: i32 = 50_000;
+
+/// Most Valuable Victim, Least Valuable Attacker: captures of bigger pieces first, and among
+/// those the ones made with the smallest piece. Zero for moves that capture nothing.
+pub fn mvv_lva(board: &Board, r#move: &Move) -> i32 {
+    let (from, to) = match r#move {
+        Move::Castle(_, _) => return 0,
+        Move::Simple(from, to) | Move::EnPassant(from, to) | Move::Promotion(from, to, _) => {
+            (from, to)
+        }
+    };
+    let victim = match r#move {
+        Move::EnPassant(_, _) => Kind::Pawn,
+        _ => match board.get_piece(to) {
+            Some(piece) => piece.kind(),
+            None => return 0,
+        },
+    };
+    let attacker = board
+        .get_piece(from)
+        .map_or(Kind::Pawn, |piece| piece.kind());
+    return exchange_value(victim) * 10 - exchange_value(attacker) / 10;
+}
+
+/// The squares a move goes from and to, with castling counted as a king move.
+fn move_squares(r#move: &Move) -> (usize, usize) {
+    return match r#move {
+        Move::Simple(from, to) | Move::EnPassant(from, to) | Move::Promotion(from, to, _) => {
+            (bitboard::square_index(from), bitboard::square_index(to))
+        }
+        Move::Castle(color, side) => {
+            let rank = color.home_rank();
+            (
+                bitboard::square_index(&Location {
+                    rank: rank,
+                    file: 4,
+                }),
+                bitboard::square_index(&Location {
+                    rank: rank,
+                    file: side.king_dest_file(),
+                }),
+            )
+        }
+    };
+}
+
+/// Puts the moves most likely to cause a cutoff first: the move the transposition table
+/// remembers, then captures by MVV-LVA, queen promotions, the killer moves for the ply, and
+/// finally quiet moves by how often they have caused cutoffs before.
+pub struct MoveOrderer {
+    killers: Vec<[Option<Move>; 2]>,
+    /// Indexed by color, from square and to square.
+    history: Vec<i32>,
+    /// Forget everything learned from earlier searches when a new one starts, so that the same
+    /// search always orders moves, and so finds its result, the same way.
+    pub deterministic: bool,
+}
+
+impl MoveOrderer {
+    pub fn new() -> Self {
+        return MoveOrderer {
+            killers: vec![[None; 2]; MAX_PLY],
+            history: vec![0; 2 * 64 * 64],
+            deterministic: false,
+        };
+    }
+
+    /// Forgets all killer moves and history.
+    pub fn clear(&mut self) {
+        self.killers
+            .iter_mut()
+            .for_each(|killers| *killers = [None; 2]);
+        self.history.iter_mut().for_each(|score| *score = 0);
+    }
+
+    /// Clears the killer moves, and either clears the history table or, unless deterministic,
+    /// halves it so that older results count for less.
+    pub fn new_search(&mut self) {
+        self.killers
+            .iter_mut()
+            .for_each(|killers| *killers = [None; 2]);
+        if self.deterministic {
+            self.history.iter_mut().for_each(|score| *score = 0);
+        } else {
+            self.history.iter_mut().for_each(|score| *score /= 2);
+        }
+    }
+
+    fn history_index(color: Color, r#move: &Move) -> usize {
+        let (from, to) = move_squares(r#move);
+        return color.index() * 64 * 64 + from * 64 + to;
+    }
+
+    pub fn score(&self, board: &Board, r#move: &Move, hash_move: Option<Move>, ply: usize) -> i32 {
+        if Some(*r#move) == hash_move {
+            return HASH_MOVE;
+        }
+        if board.is_capture(r#move) {
+            return CAPTURE + mvv_lva(board, r#move);
+        }
+        if let Move::Promotion(_, _, Kind::Queen) = r#move {
+            return PROMOTION;
+        }
+        if let Some(killers) = self.killers.get(ply) {
+            for (killer, score) in killers.iter().zip(KILLERS.iter()) {
+                if Some(*r#move) == *killer {
+                    return *score;
+                }
+            }
+        }
+        return self.history[MoveOrderer::history_index(board.to_move, r#move)];
+    }
+
+    /// Sorts `moves` best first. Ties keep the order they were generated in, which does not
+    /// change from run to run.
+    pub fn order(&self, board: &Board, moves: &mut [Move], hash_move: Option<Move>, ply: usize) {
+        moves.sort_by_cached_key(|r#move| -self.score(board, r#move, hash_move, ply));
+    }
+
+    /// Remembers that `move` caused a beta cutoff `depth` plies from the horizon. Only quiet
+    /// moves are recorded, as captures are already ordered well.
+    pub fn record_cutoff(&mut self, board: &Board, r#move: &Move, depth: u32, ply: usize) {
+        if board.is_capture(r#move) || matches!(r#move, Move::Promotion(_, _, _)) {
+            return;
+        }
+        if let Some(killers) = self.killers.get_mut(ply) {
+            if killers[0] != Some(*r#move) {
+                killers[1] = killers[0];
+                killers[0] = Some(*r#move);
+            }
+        }
+        let index = MoveOrderer::history_index(board.to_move, r#move);
+        self.history[index] += (depth * depth) as i32;
+        if self.history[index] >= HISTORY_LIMIT {
+            self.history.iter_mut().for_each(|score| *score /= 2);
+        }
+    }
+}
+
+impl Default for MoveOrderer {
+    fn default() -> Self {
+        return MoveOrderer::new();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ordered(
+        board: &Board,
+        orderer: &MoveOrderer,
+        hash_move: Option<Move>,
+        ply: usize,
+    ) -> Vec<String> {
+        let mut moves = board.legal_moves();
+        orderer.order(board, &mut moves, hash_move, ply);
+        return moves.iter().map(|r#move| board.to_pgn(r#move)).collect();
+    }
+
+    const FEN: &str = "4k3/8/2q1r3/3P4/8/8/2Q4K/8 w - - 0 1";
+
+    #[test]
+    fn orders_captures_by_victim_then_attacker() {
+        let board = Board::from_fen(FEN).unwrap();
+        let orderer = MoveOrderer::new();
+        assert_eq!(
+            ordered(&board, &orderer, None, 0)[0..3].to_vec(),
+            vec!["dxc6", "Qxc6+", "dxe6"]
+        );
+        let hash_move = board.parse_pgn_move("Kh1").unwrap();
+        assert_eq!(ordered(&board, &orderer, Some(hash_move), 0)[0], "Kh1");
+    }
+
+    #[test]
+    fn remembers_killers_and_history() {
+        let board = Board::from_fen(FEN).unwrap();
+        let mut orderer = MoveOrderer::new();
+        let queen_move = board.parse_pgn_move("Qc3").unwrap();
+        let king_move = board.parse_pgn_move("Kg1").unwrap();
+        orderer.record_cutoff(&board, &queen_move, 2, 3);
+        orderer.record_cutoff(&board, &queen_move, 2, 3);
+        orderer.record_cutoff(&board, &king_move, 2, 3);
+        // Captures are never recorded
+        let capture = board.parse_pgn_move("dxc6").unwrap();
+        orderer.record_cutoff(&board, &capture, 2, 3);
+
+        assert_eq!(
+            ordered(&board, &orderer, None, 3)[3..5].to_vec(),
+            vec!["Kg1", "Qc3"]
+        );
+        assert_eq!(
+            ordered(&board, &orderer, None, 0)[3..5].to_vec(),
+            vec!["Qc3", "Kg1"]
+        );
+
+        orderer.new_search();
+        assert_eq!(orderer.score(&board, &queen_move, None, 3), 4);
+        orderer.deterministic = true;
+        orderer.new_search();
+        assert_eq!(orderer.score(&board, &queen_move, None, 3), 0);
+    }
+}
@@ -0,0 +1,666 @@
+use crate::board::{Board, Color, FenError, Move};
+use crate::error::Error;
+use std::io::{BufRead, Write};
+
+/// The tags every exported game carries, in the order they must appear.
+pub const SEVEN_TAG_ROSTER: [&str; 7] =
+    ["Event", "Site", "Date", "Round", "White", "Black", "Result"];
+
+/// Export format lines must stay below 80 characters.
+const MAX_LINE_LENGTH: usize = 79;
+
+#[derive(Debug)]
+pub enum PgnError {
+    Io(std::io::Error),
+    InvalidTag(String),
+    UnexpectedToken(String),
+    UnterminatedComment,
+    Fen(FenError),
+    Move(Error),
+}
+
+impl std::fmt::Display for PgnError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        return match self {
+            PgnError::Io(error) => write!(f, "Could not read PGN: {}", error),
+            PgnError::InvalidTag(tag) => write!(f, "Invalid tag pair: {}", tag),
+            PgnError::UnexpectedToken(token) => write!(f, "Unexpected token: {}", token),
+            PgnError::UnterminatedComment => write!(f, "Unterminated comment"),
+            PgnError::Fen(error) => write!(f, "Invalid FEN tag: {}", error),
+            PgnError::Move(error) => write!(f, "{}", error),
+        };
+    }
+}
+
+impl std::error::Error for PgnError {}
+
+impl From<std::io::Error> for PgnError {
+    fn from(error: std::io::Error) -> Self {
+        return PgnError::Io(error);
+    }
+}
+
+impl From<FenError> for PgnError {
+    fn from(error: FenError) -> Self {
+        return PgnError::Fen(error);
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct GameMove {
+    /// The move as written in the source, without move suffix annotations like `!?`.
+    pub san: String,
+    pub r#move: Move,
+    /// Numeric annotation glyphs, with `!`, `?` etc. converted to their `$n` equivalents.
+    pub nags: Vec<u8>,
+    /// Comments that appear before this move with no move in between.
+    pub leading_comments: Vec<String>,
+    pub comments: Vec<String>,
+    /// Alternatives to this move, each starting from the position before it.
+    pub variations: Vec<Vec<GameMove>>,
+}
+
+#[derive(Clone, Debug)]
+pub struct Game {
+    pub tags: Vec<(String, String)>,
+    pub moves: Vec<GameMove>,
+    /// The game termination marker, if the movetext had one.
+    pub result: Option<String>,
+}
+
+impl Game {
+    pub fn new() -> Self {
+        return Self {
+            tags: vec![],
+            moves: vec![],
+            result: None,
+        };
+    }
+
+    pub fn set_tag(&mut self, name: &str, value: &str) {
+        for (tag_name, tag_value) in self.tags.iter_mut() {
+            if tag_name == name {
+                *tag_value = String::from(value);
+                return;
+            }
+        }
+        self.tags.push((String::from(name), String::from(value)));
+    }
+
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        for (tag_name, value) in &self.tags {
+            if tag_name == name {
+                return Some(value);
+            }
+        }
+        return None;
+    }
+
+    /// The position the game starts from, honouring a `FEN` tag if there is one.
+    pub fn starting_board(&self) -> Result<Board, FenError> {
+        return match self.tag("FEN") {
+            Some(fen) => Board::from_fen(fen),
+            None => Ok(Board::default()),
+        };
+    }
+
+    /// The position after the last move of the main line.
+    pub fn final_board(&self) -> Result<Board, FenError> {
+        let mut board = self.starting_board()?;
+        for game_move in &self.moves {
+            board.apply_move(game_move.r#move);
+        }
+        return Ok(board);
+    }
+}
+
+impl Game {
+    /// Writes the game in PGN export format: the seven tag roster first, then any other tags,
+    /// then movetext with SAN regenerated from the board so it is always disambiguated correctly.
+    pub fn write_pgn<W: Write>(&self, writer: &mut W) -> Result<(), PgnError> {
+        let result = match (&self.result, self.tag("Result")) {
+            (Some(result), _) => result.as_str(),
+            (None, Some(result)) => result,
+            (None, None) => "*",
+        };
+        for name in SEVEN_TAG_ROSTER.iter() {
+            let value = match *name {
+                "Result" => result,
+                "Date" => self.tag(name).unwrap_or("????.??.??"),
+                _ => self.tag(name).unwrap_or("?"),
+            };
+            writeln!(writer, "[{} \"{}\"]", name, escape_tag_value(value))?;
+        }
+        for (name, value) in &self.tags {
+            if !SEVEN_TAG_ROSTER.contains(&name.as_str()) {
+                writeln!(writer, "[{} \"{}\"]", name, escape_tag_value(value))?;
+            }
+        }
+        writeln!(writer)?;
+
+        let mut tokens: Vec<String> = vec![];
+        movetext_tokens(&self.starting_board()?, &self.moves, &mut tokens);
+        tokens.push(String::from(result));
+
+        let mut line = String::new();
+        for token in tokens {
+            if !line.is_empty() && line.len() + 1 + token.len() > MAX_LINE_LENGTH {
+                writeln!(writer, "{}", line)?;
+                line.clear();
+            }
+            if !line.is_empty() {
+                line.push(' ');
+            }
+            line.push_str(&token);
+        }
+        writeln!(writer, "{}", line)?;
+        writeln!(writer)?;
+        return Ok(());
+    }
+
+    pub fn to_pgn(&self) -> Result<String, PgnError> {
+        let mut buffer: Vec<u8> = vec![];
+        self.write_pgn(&mut buffer)?;
+        return Ok(String::from_utf8(buffer).expect("PGN export is always valid UTF-8"));
+    }
+}
+
+impl Default for Game {
+    fn default() -> Self {
+        return Self::new();
+    }
+}
+
+fn escape_tag_value(value: &str) -> String {
+    return value.replace('\\', "\\\\").replace('"', "\\\"");
+}
+
+/// Comments are split into words so long ones can be wrapped like the rest of the movetext.
+fn push_comment_tokens(comment: &str, tokens: &mut Vec<String>) {
+    let words: Vec<&str> = comment.split_whitespace().collect();
+    if words.is_empty() {
+        tokens.push(String::from("{}"));
+        return;
+    }
+    let start = tokens.len();
+    for word in words {
+        tokens.push(String::from(word));
+    }
+    tokens[start].insert(0, '{');
+    tokens.last_mut().unwrap().push('}');
+}
+
+fn movetext_tokens(board: &Board, moves: &[GameMove], tokens: &mut Vec<String>) {
+    let mut board = board.clone();
+    // Black moves need their own number at the start of a line of play or after an interruption
+    let mut needs_number = true;
+    for game_move in moves {
+        for comment in &game_move.leading_comments {
+            push_comment_tokens(comment, tokens);
+            needs_number = true;
+        }
+        // Move numbers are kept on the same line as the move they number
+        let san = board.to_pgn(&game_move.r#move);
+        tokens.push(match board.to_move {
+            Color::White => format!("{}. {}", board.fullmove_number, san),
+            Color::Black if needs_number => format!("{}... {}", board.fullmove_number, san),
+            Color::Black => san,
+        });
+        needs_number = false;
+        for nag in &game_move.nags {
+            tokens.push(format!("${}", nag));
+        }
+        for comment in &game_move.comments {
+            push_comment_tokens(comment, tokens);
+            needs_number = true;
+        }
+        for variation in &game_move.variations {
+            let start = tokens.len();
+            movetext_tokens(&board, variation, tokens);
+            if tokens.len() > start {
+                tokens[start].insert(0, '(');
+                tokens.last_mut().unwrap().push(')');
+            }
+            needs_number = true;
+        }
+        board.apply_move(game_move.r#move);
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+enum Token {
+    TagPair(String, String),
+    Symbol(String),
+    Nag(u8),
+    Comment(String),
+    VariationStart,
+    VariationEnd,
+    Result(String),
+}
+
+const RESULTS: [&str; 4] = ["1-0", "0-1", "1/2-1/2", "*"];
+
+fn is_symbol_char(c: char) -> bool {
+    return c.is_ascii_alphanumeric() || "_+#=:-/!?".contains(c);
+}
+
+fn suffix_to_nag(suffix: &str) -> Option<u8> {
+    return match suffix {
+        "!" => Some(1),
+        "?" => Some(2),
+        "!!" => Some(3),
+        "??" => Some(4),
+        "!?" => Some(5),
+        "?!" => Some(6),
+        _ => None,
+    };
+}
+
+struct Lexer<R: BufRead> {
+    reader: R,
+    line: Vec<char>,
+    pos: usize,
+    peeked: Option<Token>,
+}
+
+impl<R: BufRead> Lexer<R> {
+    fn new(reader: R) -> Self {
+        return Self {
+            reader: reader,
+            line: vec![],
+            pos: 0,
+            peeked: None,
+        };
+    }
+
+    /// Makes sure there is an unread character, reading lines as needed. Returns false at EOF.
+    fn fill(&mut self) -> Result<bool, PgnError> {
+        while self.pos >= self.line.len() {
+            let mut buffer = String::new();
+            if self.reader.read_line(&mut buffer)? == 0 {
+                return Ok(false);
+            }
+            // A % in the first column escapes the whole line
+            if buffer.starts_with('%') {
+                continue;
+            }
+            self.line = buffer.chars().collect();
+            self.pos = 0;
+        }
+        return Ok(true);
+    }
+
+    fn peek_char(&mut self) -> Result<Option<char>, PgnError> {
+        if !self.fill()? {
+            return Ok(None);
+        }
+        return Ok(Some(self.line[self.pos]));
+    }
+
+    fn next_char(&mut self) -> Result<Option<char>, PgnError> {
+        let result = self.peek_char()?;
+        if result.is_some() {
+            self.pos += 1;
+        }
+        return Ok(result);
+    }
+
+    fn push_back(&mut self, token: Token) {
+        self.peeked = Some(token);
+    }
+
+    fn next_token(&mut self) -> Result<Option<Token>, PgnError> {
+        if let Some(token) = self.peeked.take() {
+            return Ok(Some(token));
+        }
+        loop {
+            let c = match self.next_char()? {
+                Some(c) => c,
+                None => return Ok(None),
+            };
+            match c {
+                c if c.is_whitespace() || c == '.' => continue,
+                '[' => return Ok(Some(self.read_tag_pair()?)),
+                '{' => {
+                    let mut comment = String::new();
+                    loop {
+                        match self.next_char()? {
+                            Some('}') => break,
+                            Some(c) => comment.push(c),
+                            None => return Err(PgnError::UnterminatedComment),
+                        }
+                    }
+                    return Ok(Some(Token::Comment(String::from(comment.trim()))));
+                }
+                ';' => {
+                    let rest: String = self.line[self.pos..].iter().collect();
+                    self.pos = self.line.len();
+                    return Ok(Some(Token::Comment(String::from(rest.trim()))));
+                }
+                '(' => return Ok(Some(Token::VariationStart)),
+                ')' => return Ok(Some(Token::VariationEnd)),
+                '*' => return Ok(Some(Token::Result(String::from("*")))),
+                '$' => {
+                    let mut digits = String::new();
+                    while let Some(c) = self.peek_char()? {
+                        if !c.is_ascii_digit() {
+                            break;
+                        }
+                        digits.push(c);
+                        self.pos += 1;
+                    }
+                    return match digits.parse::<u8>() {
+                        Ok(nag) => Ok(Some(Token::Nag(nag))),
+                        Err(_) => Err(PgnError::UnexpectedToken(format!("${}", digits))),
+                    };
+                }
+                c if is_symbol_char(c) => {
+                    let mut symbol = String::new();
+                    symbol.push(c);
+                    while let Some(c) = self.peek_char()? {
+                        if !is_symbol_char(c) {
+                            break;
+                        }
+                        symbol.push(c);
+                        self.pos += 1;
+                    }
+                    if RESULTS.contains(&symbol.as_str()) {
+                        return Ok(Some(Token::Result(symbol)));
+                    }
+                    // Move numbers carry no information, the board knows whose move it is
+                    if symbol.chars().all(|c| c.is_ascii_digit()) {
+                        continue;
+                    }
+                    return Ok(Some(Token::Symbol(symbol)));
+                }
+                c => return Err(PgnError::UnexpectedToken(c.to_string())),
+            }
+        }
+    }
+
+    fn read_tag_pair(&mut self) -> Result<Token, PgnError> {
+        let mut raw = String::new();
+        let mut in_string = false;
+        let mut escaped = false;
+        loop {
+            let c = match self.next_char()? {
+                Some(c) => c,
+                None => return Err(PgnError::InvalidTag(raw)),
+            };
+            if !in_string && c == ']' {
+                break;
+            }
+            if in_string && !escaped && c == '\\' {
+                escaped = true;
+                raw.push(c);
+                continue;
+            }
+            if !escaped && c == '"' {
+                in_string = !in_string;
+            }
+            escaped = false;
+            raw.push(c);
+        }
+
+        let trimmed = raw.trim();
+        let name_end = trimmed.find(char::is_whitespace).unwrap_or(trimmed.len());
+        let name = &trimmed[..name_end];
+        let quoted = trimmed[name_end..].trim();
+        if name.is_empty() || quoted.len() < 2 || !quoted.starts_with('"') || !quoted.ends_with('"')
+        {
+            return Err(PgnError::InvalidTag(raw));
+        }
+        let value = quoted[1..quoted.len() - 1]
+            .replace("\\\"", "\"")
+            .replace("\\\\", "\\");
+        return Ok(Token::TagPair(String::from(name), value));
+    }
+}
+
+/// Reads games one at a time from a PGN source that may hold any number of them.
+pub struct PgnReader<R: BufRead> {
+    lexer: Lexer<R>,
+}
+
+impl<R: BufRead> PgnReader<R> {
+    pub fn new(reader: R) -> Self {
+        return Self {
+            lexer: Lexer::new(reader),
+        };
+    }
+
+    fn read_game(&mut self) -> Result<Option<Game>, PgnError> {
+        loop {
+            let mut tags: Vec<(String, String)> = vec![];
+            loop {
+                match self.lexer.next_token()? {
+                    Some(Token::TagPair(name, value)) => tags.push((name, value)),
+                    Some(token) => {
+                        self.lexer.push_back(token);
+                        break;
+                    }
+                    None if tags.is_empty() => return Ok(None),
+                    None => break,
+                }
+            }
+
+            let mut game = Game {
+                tags: tags,
+                moves: vec![],
+                result: None,
+            };
+            let board = game.starting_board()?;
+            let (moves, result) = self.read_line(board, 0)?;
+            game.moves = moves;
+            game.result = result;
+            // Stray comments between games are not a game of their own
+            if game.tags.is_empty() && game.moves.is_empty() && game.result.is_none() {
+                continue;
+            }
+            return Ok(Some(game));
+        }
+    }
+
+    /// Reads moves from `board` until the end of the current variation or, at the top level,
+    /// the end of the game.
+    fn read_line(
+        &mut self,
+        mut board: Board,
+        depth: usize,
+    ) -> Result<(Vec<GameMove>, Option<String>), PgnError> {
+        let mut moves: Vec<GameMove> = vec![];
+        let mut pending_comments: Vec<String> = vec![];
+        loop {
+            let token = match self.lexer.next_token()? {
+                Some(token) => token,
+                None if depth == 0 => return Ok((moves, None)),
+                None => return Err(PgnError::UnexpectedToken(String::from("end of file"))),
+            };
+            match token {
+                Token::Symbol(symbol) => {
+                    let san = symbol.trim_end_matches(&['!', '?'][..]);
+                    let suffix = &symbol[san.len()..];
+                    let r#move = board.parse_pgn_move(san).map_err(PgnError::Move)?;
+                    let mut game_move = GameMove {
+                        san: String::from(san),
+                        r#move: r#move,
+                        nags: vec![],
+                        leading_comments: std::mem::take(&mut pending_comments),
+                        comments: vec![],
+                        variations: vec![],
+                    };
+                    if let Some(nag) = suffix_to_nag(suffix) {
+                        game_move.nags.push(nag);
+                    }
+                    moves.push(game_move);
+                    board.apply_move(r#move);
+                }
+                Token::Nag(nag) => match moves.last_mut() {
+                    Some(last) => last.nags.push(nag),
+                    None => return Err(PgnError::UnexpectedToken(format!("${}", nag))),
+                },
+                Token::Comment(comment) => match moves.last_mut() {
+                    Some(last) => last.comments.push(comment),
+                    None => pending_comments.push(comment),
+                },
+                Token::VariationStart => {
+                    // A variation replaces the move before it, so it starts one move back
+                    if moves.is_empty() {
+                        return Err(PgnError::UnexpectedToken(String::from("(")));
+                    }
+                    let mut variation_board = board.clone();
+                    variation_board.undo_move();
+                    let (variation, _) = self.read_line(variation_board, depth + 1)?;
+                    if let Some(last) = moves.last_mut() {
+                        last.variations.push(variation);
+                    }
+                }
+                Token::VariationEnd if depth > 0 => return Ok((moves, None)),
+                Token::VariationEnd => return Err(PgnError::UnexpectedToken(String::from(")"))),
+                Token::Result(result) if depth == 0 => return Ok((moves, Some(result))),
+                Token::Result(result) => return Err(PgnError::UnexpectedToken(result)),
+                Token::TagPair(name, value) if depth == 0 => {
+                    // The next game's tags began without a result for this one
+                    self.lexer.push_back(Token::TagPair(name, value));
+                    return Ok((moves, None));
+                }
+                Token::TagPair(name, _) => return Err(PgnError::UnexpectedToken(name)),
+            }
+        }
+    }
+
+    /// Discards the rest of a game that failed to parse so the next one can be read.
+    fn skip_game(&mut self) {
+        loop {
+            match self.lexer.next_token() {
+                Ok(Some(Token::TagPair(name, value))) => {
+                    self.lexer.push_back(Token::TagPair(name, value));
+                    return;
+                }
+                Ok(Some(_)) => continue,
+                Ok(None) => return,
+                Err(PgnError::Io(_)) => return,
+                Err(_) => continue,
+            }
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for PgnReader<R> {
+    type Item = Result<Game, PgnError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        return match self.read_game() {
+            Ok(Some(game)) => Some(Ok(game)),
+            Ok(None) => None,
+            Err(error) => {
+                self.skip_game();
+                Some(Err(error))
+            }
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::Location;
+
+    const GAMES: &str = r#"[Event "F/S Return Match"]
+[Site "Belgrade, Serbia JUG"]
+[Date "1992.11.04"]
+[Round "29"]
+[White "Fischer, Robert J."]
+[Black "Spassky, Boris V."]
+[Result "1/2-1/2"]
+
+1. e4 e5 2. Nf3 Nc6 3. Bb5 {This opening is called the Ruy Lopez.} 3... a6
+4. Ba4 Nf6 5. O-O Be7 6. Re1 b5 7. Bb3 d6 8. c3 O-O 9. h3 Nb8 10. d4 Nbd7
+11. c4 c6 12. cxb5 axb5 13. Nc3 Bb7 14. Bg5 b4 15. Nb1 h6 16. Bh4 c5 17. dxe5
+Nxe4 18. Bxe7 Qxe7 19. exd6 Qf6 20. Nbd2 Nxd6 21. Nc4 Nxc4 22. Bxc4 Nb6
+23. Ne5 Rae8 24. Bxf7+ Rxf7 25. Nxf7 Rxe1+ 26. Qxe1 Kxf7 27. Qe3 Qg5 28. Qxg5
+hxg5 29. b3 Ke6 30. a3 Kd6 31. axb4 cxb4 32. Ra5 Nd5 33. f3 Bc8 34. Kf2 Bf5
+35. Ra7 g6 36. Ra6+ Kc5 37. Ke1 Nf4 38. g3 Nxh3 39. Kd2 Kb5 40. Rd6 Kc5 41. Ra6
+Nf2 42. g4 Bd3 43. Re6 1/2-1/2
+
+% An escaped line that should be ignored
+[Event "Variations"]
+[FEN "4k3/8/8/8/8/8/4P3/4K3 w - - 0 1"]
+
+1. e4!? $14 (1. e3 ; a quieter move
+Kd7 (1... Ke7 2. Kd2) 2. Kd2) 1... Kd7 2. Kd2 *
+"#;
+
+    #[test]
+    fn reads_multiple_games() {
+        let games: Vec<Game> = PgnReader::new(GAMES.as_bytes())
+            .collect::<Result<Vec<Game>, PgnError>>()
+            .unwrap();
+        assert_eq!(games.len(), 2);
+
+        let first = &games[0];
+        assert_eq!(first.tags.len(), 7);
+        assert_eq!(first.tag("White"), Some("Fischer, Robert J."));
+        assert_eq!(first.result.as_deref(), Some("1/2-1/2"));
+        assert_eq!(first.moves.len(), 85);
+        assert_eq!(
+            first.moves[4].comments,
+            vec![String::from("This opening is called the Ruy Lopez.")]
+        );
+        assert_eq!(
+            first.final_board().unwrap().to_fen(),
+            "8/8/4R1p1/2k3p1/1p4P1/1P1b1P2/3K1n2/8 b - - 2 43"
+        );
+    }
+
+    #[test]
+    fn reads_annotations_and_variations() {
+        let game = PgnReader::new(GAMES.as_bytes()).nth(1).unwrap().unwrap();
+        assert_eq!(game.result.as_deref(), Some("*"));
+        assert_eq!(game.moves.len(), 3);
+
+        let first = &game.moves[0];
+        assert_eq!(first.san, "e4");
+        assert_eq!(first.nags, vec![5, 14]);
+        assert_eq!(first.variations.len(), 1);
+
+        let variation = &first.variations[0];
+        assert_eq!(variation.len(), 3);
+        assert_eq!(
+            variation[0].r#move,
+            Move::Simple(Location { rank: 1, file: 4 }, Location { rank: 2, file: 4 })
+        );
+        assert_eq!(variation[0].comments, vec![String::from("a quieter move")]);
+        assert_eq!(variation[1].variations[0].len(), 2);
+    }
+
+    #[test]
+    fn exports_wrapped_movetext_with_roster_tags() {
+        let game = PgnReader::new(GAMES.as_bytes()).next().unwrap().unwrap();
+        let exported = game.to_pgn().unwrap();
+        assert!(exported.starts_with("[Event \"F/S Return Match\"]\n[Site "));
+        assert!(exported.contains("\n\n1. e4 e5 2. Nf3 Nc6 3. Bb5 {This opening is called the Ruy"));
+        assert!(exported.contains("20. Nbd2 "));
+        assert!(exported.ends_with(" 43. Re6 1/2-1/2\n\n"));
+        for line in exported.lines() {
+            assert!(line.len() < 80, "Line too long: {}", line);
+        }
+
+        let reread = PgnReader::new(exported.as_bytes()).next().unwrap().unwrap();
+        assert_eq!(reread.tags, game.tags);
+        assert_eq!(reread.moves.len(), game.moves.len());
+    }
+
+    #[test]
+    fn exports_variations_and_default_tags() {
+        let game = PgnReader::new(GAMES.as_bytes()).nth(1).unwrap().unwrap();
+        let exported = game.to_pgn().unwrap();
+        assert!(exported.starts_with("[Event \"Variations\"]\n[Site \"?\"]\n[Date \"????.??.??\"]"));
+        assert!(exported.contains("[Result \"*\"]\n[FEN "));
+        let movetext = exported.split("\n\n").nth(1).unwrap().replace('\n', " ");
+        assert_eq!(
+            movetext.trim_end(),
+            "1. e4 $5 $14 (1. e3 {a quieter move} 1... Kd7 (1... Ke7 2. Kd2) 2. Kd2) 1... Kd7 2. Kd2 *"
+        );
+    }
+}
@@ -0,0 +1,515 @@
+use crate::board::{Board, Kind, Move};
+use crate::evaluate::evaluate;
+use crate::ordering::MoveOrderer;
+use crate::see::see;
+use crate::transposition::{self, Bound, TranspositionTable};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// The score of delivering mate right now. Mate `n` plies away scores `MATE_SCORE - n`, and being
+/// mated scores the negation.
+pub(crate) const MATE_SCORE: i32 = 30_000;
+/// The deepest the search goes, counting quiescence, in plies from the root.
+pub(crate) const MAX_PLY: usize = 128;
+const INFINITY: i32 = MATE_SCORE + 1;
+
+/// Whether `score` means one side can force mate.
+pub(crate) fn is_mate_score(score: i32) -> bool {
+    return score.abs() >= MATE_SCORE - MAX_PLY as i32;
+}
+
+/// When to stop searching. `None` means no limit of that kind; with no limits at all the search
+/// runs to the maximum depth.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct SearchLimits {
+    pub depth: Option<u32>,
+    pub nodes: Option<u64>,
+    pub time: Option<Duration>,
+}
+
+/// The outcome of the deepest search iteration that finished.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SearchResult {
+    /// `None` only when the side to move has no legal moves.
+    pub best_move: Option<Move>,
+    /// From the point of view of the side to move: in centipawns, or a mate score when either
+    /// side can force mate, which `mate_in` turns into moves.
+    pub score: i32,
+    pub depth: u32,
+    pub nodes: u64,
+    /// The expected line of play, starting with `best_move`.
+    pub pv: Vec<Move>,
+}
+
+impl SearchResult {
+    /// The number of moves to mate when the score says one side can force it, positive when the
+    /// side to move mates and negative when it gets mated.
+    pub fn mate_in(&self) -> Option<i32> {
+        if !is_mate_score(self.score) {
+            return None;
+        }
+        let plies = MATE_SCORE - self.score.abs();
+        if self.score > 0 {
+            return Some((plies + 1) / 2);
+        }
+        return Some(-plies / 2);
+    }
+}
+
+/// What `Searcher::search_with_progress` reports after each iteration.
+pub struct Progress<'a> {
+    pub result: &'a SearchResult,
+    pub elapsed: Duration,
+    /// How full the transposition table is, in parts per thousand.
+    pub hashfull: u32,
+}
+
+/// Searches for the best move in `board` within `limits`. The board is returned to its original
+/// position afterwards.
+pub fn search(board: &mut Board, limits: SearchLimits) -> SearchResult {
+    return Searcher::default().search(board, limits);
+}
+
+/// A negamax alpha-beta search with iterative deepening. The transposition table is kept from
+/// one search to the next.
+pub struct Searcher {
+    table: TranspositionTable,
+    ordering: MoveOrderer,
+    limits: SearchLimits,
+    start: Instant,
+    nodes: u64,
+    stopped: bool,
+    stop: Arc<AtomicBool>,
+}
+
+impl Searcher {
+    /// A searcher whose transposition table uses `hash_megabytes` of memory.
+    pub fn new(hash_megabytes: usize) -> Self {
+        return Searcher {
+            table: TranspositionTable::new(hash_megabytes),
+            ordering: MoveOrderer::new(),
+            limits: SearchLimits::default(),
+            start: Instant::now(),
+            nodes: 0,
+            stopped: false,
+            stop: Arc::new(AtomicBool::new(false)),
+        };
+    }
+
+    pub fn table(&self) -> &TranspositionTable {
+        return &self.table;
+    }
+
+    pub(crate) fn table_mut(&mut self) -> &mut TranspositionTable {
+        return &mut self.table;
+    }
+
+    /// Makes every search independent of the ones before it, so that the same position and
+    /// limits always give the same result. The transposition table is cleared before each
+    /// search too. Time limits still make results vary with the speed of the machine.
+    pub fn set_deterministic(&mut self, deterministic: bool) {
+        self.ordering.deterministic = deterministic;
+    }
+
+    /// Forgets everything learned from earlier searches, as before a new game.
+    pub fn clear(&mut self) {
+        self.table.clear();
+        self.ordering.clear();
+    }
+
+    /// A flag that makes the search return as soon as possible once set, for stopping it from
+    /// another thread. The search never clears it; whoever sets it should do so before the next
+    /// search.
+    pub fn stop_handle(&self) -> Arc<AtomicBool> {
+        return self.stop.clone();
+    }
+
+    pub fn search(&mut self, board: &mut Board, limits: SearchLimits) -> SearchResult {
+        return self.search_with_progress(board, limits, |_| {});
+    }
+
+    /// Searches like `search`, calling `progress` each time an iteration finishes.
+    pub fn search_with_progress<F: FnMut(&Progress)>(
+        &mut self,
+        board: &mut Board,
+        limits: SearchLimits,
+        mut progress: F,
+    ) -> SearchResult {
+        self.limits = limits;
+        self.start = Instant::now();
+        self.nodes = 0;
+        self.stopped = false;
+        self.table.new_search();
+        self.ordering.new_search();
+        if self.ordering.deterministic {
+            self.table.clear();
+        }
+
+        let root_moves = board.legal_moves_in_place();
+        let mut result = SearchResult {
+            best_move: root_moves.first().copied(),
+            score: 0,
+            depth: 0,
+            nodes: 0,
+            pv: root_moves.first().copied().into_iter().collect(),
+        };
+        if root_moves.is_empty() {
+            if board.in_check(board.to_move) {
+                result.score = -MATE_SCORE;
+            }
+            return result;
+        }
+
+        let max_depth = self
+            .limits
+            .depth
+            .unwrap_or(MAX_PLY as u32)
+            .min(MAX_PLY as u32);
+        for depth in 1..=max_depth {
+            let mut pv: Vec<Move> = vec![];
+            let score = self.negamax(board, depth, 0, -INFINITY, INFINITY, &mut pv);
+            if self.stopped {
+                break;
+            }
+            result.best_move = pv.first().copied();
+            result.score = score;
+            result.depth = depth;
+            result.nodes = self.nodes;
+            result.pv = pv;
+            progress(&Progress {
+                result: &result,
+                elapsed: self.start.elapsed(),
+                hashfull: self.table.hashfull(),
+            });
+            // Nothing deeper can improve on a forced mate that has been found
+            if is_mate_score(score) && MATE_SCORE - score.abs() <= depth as i32 {
+                break;
+            }
+        }
+        result.nodes = self.nodes;
+        return result;
+    }
+
+    /// Whether a node or time limit has been reached. The clock is only read every so often.
+    fn should_stop(&mut self) -> bool {
+        if self.stop.load(Ordering::Relaxed) {
+            self.stopped = true;
+        }
+        if let Some(nodes) = self.limits.nodes {
+            if self.nodes >= nodes {
+                self.stopped = true;
+            }
+        }
+        if let Some(time) = self.limits.time {
+            if self.nodes % 1024 == 0 && self.start.elapsed() >= time {
+                self.stopped = true;
+            }
+        }
+        return self.stopped;
+    }
+
+    fn negamax(
+        &mut self,
+        board: &mut Board,
+        depth: u32,
+        ply: i32,
+        mut alpha: i32,
+        beta: i32,
+        pv: &mut Vec<Move>,
+    ) -> i32 {
+        self.nodes += 1;
+        pv.clear();
+        if ply > 0 {
+            if self.should_stop() {
+                return 0;
+            }
+            if board.repetitions() >= 2 || board.is_insufficient_material() {
+                return 0;
+            }
+            // Mate on the move that reaches the fifty-move limit still stands
+            if board.halfmove_clock >= 100 {
+                if board.in_check(board.to_move) && board.legal_moves_in_place().is_empty() {
+                    return -MATE_SCORE + ply;
+                }
+                return 0;
+            }
+        }
+        if depth == 0 {
+            return self.quiescence(board, ply, alpha, beta);
+        }
+
+        let key = board.hash();
+        let mut hash_move: Option<Move> = None;
+        if let Some(entry) = self.table.probe(key, ply) {
+            hash_move = entry.best_move;
+            // Cutting off here would lose the rest of the principal variation
+            let pv_node = beta - alpha > 1;
+            if ply > 0 && !pv_node && entry.depth >= depth {
+                match entry.bound {
+                    Bound::Exact => return entry.score,
+                    Bound::Lower if entry.score >= beta => return entry.score,
+                    Bound::Upper if entry.score <= alpha => return entry.score,
+                    _ => {}
+                }
+            }
+        }
+
+        let mut moves = board.legal_moves_in_place();
+        if moves.is_empty() {
+            if board.in_check(board.to_move) {
+                return -MATE_SCORE + ply;
+            }
+            return 0;
+        }
+        if ply >= MAX_PLY as i32 {
+            return evaluate(board);
+        }
+        self.ordering
+            .order(board, &mut moves, hash_move, ply as usize);
+
+        let original_alpha = alpha;
+        let mut best = -INFINITY;
+        let mut best_move: Option<Move> = None;
+        let mut child_pv: Vec<Move> = vec![];
+        for r#move in moves {
+            board.make_move(r#move);
+            let score = -self.negamax(board, depth - 1, ply + 1, -beta, -alpha, &mut child_pv);
+            board.unmake_move();
+            if self.stopped {
+                return 0;
+            }
+            if score > best {
+                best = score;
+                best_move = Some(r#move);
+                if score > alpha {
+                    alpha = score;
+                    pv.clear();
+                    pv.push(r#move);
+                    pv.append(&mut child_pv);
+                }
+                if score >= beta {
+                    self.ordering
+                        .record_cutoff(board, &r#move, depth, ply as usize);
+                    break;
+                }
+            }
+        }
+        let bound = if best >= beta {
+            Bound::Lower
+        } else if best > original_alpha {
+            Bound::Exact
+        } else {
+            Bound::Upper
+        };
+        self.table.store(key, depth, bound, best, best_move, ply);
+        return best;
+    }
+
+    /// Searches captures only, so that positions are not judged in the middle of an exchange.
+    /// The side to move may also "stand pat" on the static evaluation instead of capturing,
+    /// unless it is in check, in which case every move is searched.
+    fn quiescence(&mut self, board: &mut Board, ply: i32, mut alpha: i32, beta: i32) -> i32 {
+        if self.should_stop() {
+            return 0;
+        }
+        let in_check = board.in_check(board.to_move);
+        let mut best = -INFINITY;
+        if !in_check {
+            best = evaluate(board);
+            if best >= beta || ply >= MAX_PLY as i32 {
+                return best;
+            }
+            alpha = alpha.max(best);
+        }
+
+        let mut moves: Vec<(Move, i32)> = vec![];
+        for r#move in board.legal_moves_in_place() {
+            if in_check || board.is_capture(&r#move) || is_queen_promotion(&r#move) {
+                moves.push((r#move, see(board, &r#move)));
+            }
+        }
+        if in_check && moves.is_empty() {
+            return -MATE_SCORE + ply;
+        }
+        moves.sort_by_key(|(_, exchange)| -exchange);
+
+        for (r#move, exchange) in moves {
+            // Captures that lose material cannot raise the score above standing pat
+            if !in_check && exchange < 0 {
+                continue;
+            }
+            self.nodes += 1;
+            board.make_move(r#move);
+            let score = -self.quiescence(board, ply + 1, -beta, -alpha);
+            board.unmake_move();
+            if self.stopped {
+                return 0;
+            }
+            if score > best {
+                best = score;
+                if score > alpha {
+                    alpha = score;
+                }
+                if score >= beta {
+                    break;
+                }
+            }
+        }
+        return best;
+    }
+}
+
+fn is_queen_promotion(r#move: &Move) -> bool {
+    return matches!(r#move, Move::Promotion(_, _, Kind::Queen));
+}
+
+impl Default for Searcher {
+    fn default() -> Self {
+        return Searcher::new(transposition::DEFAULT_MEGABYTES);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn depth(depth: u32) -> SearchLimits {
+        return SearchLimits {
+            depth: Some(depth),
+            ..SearchLimits::default()
+        };
+    }
+
+    #[test]
+    fn finds_mate_in_one() {
+        let mut board = Board::from_fen("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1").unwrap();
+        let result = search(&mut board, depth(3));
+        assert_eq!(board.to_pgn(&result.best_move.unwrap()), "Ra8#");
+        assert_eq!(result.score, MATE_SCORE - 1);
+        assert_eq!(result.mate_in(), Some(1));
+        assert_eq!(result.pv, vec![result.best_move.unwrap()]);
+    }
+
+    #[test]
+    fn mate_takes_precedence_over_the_fifty_move_rule() {
+        let mut board = Board::from_fen("6k1/5ppp/8/8/8/8/8/R5K1 w - - 99 60").unwrap();
+        let result = search(&mut board, depth(3));
+        assert_eq!(board.to_pgn(&result.best_move.unwrap()), "Ra8#");
+        assert_eq!(result.score, MATE_SCORE - 1);
+    }
+
+    #[test]
+    fn finds_mate_in_two() {
+        let mut board = Board::from_fen("k7/8/8/8/8/8/7R/4K1R1 w - - 0 1").unwrap();
+        let result = search(&mut board, depth(4));
+        assert_eq!(result.score, MATE_SCORE - 3);
+        assert_eq!(result.mate_in(), Some(2));
+        assert_eq!(result.pv.len(), 3);
+
+        let mated = SearchResult {
+            score: -MATE_SCORE + 2,
+            ..result
+        };
+        assert_eq!(mated.mate_in(), Some(-1));
+    }
+
+    #[test]
+    fn wins_material_and_restores_the_board() {
+        let mut board = Board::from_fen("4k3/8/8/3q4/8/8/8/3RK3 w - - 0 1").unwrap();
+        let fen = board.to_fen();
+        let result = search(&mut board, depth(3));
+        assert_eq!(board.to_pgn(&result.best_move.unwrap()), "Rxd5");
+        assert_eq!(result.mate_in(), None);
+        assert_eq!(board.to_fen(), fen);
+        assert!(board.history().is_empty());
+        assert_eq!(board.redo_move(), None);
+    }
+
+    #[test]
+    fn respects_node_limits() {
+        let mut board = Board::default();
+        let result = search(
+            &mut board,
+            SearchLimits {
+                nodes: Some(500),
+                ..SearchLimits::default()
+            },
+        );
+        assert!(result.best_move.is_some());
+        assert!(result.nodes <= 500);
+        assert_eq!(board.to_fen(), Board::default().to_fen());
+    }
+
+    #[test]
+    fn reuses_the_transposition_table() {
+        let mut board = Board::from_fen(
+            "r4rk1/1pp1qppp/p1np1n2/2b1p1B1/2B1P1b1/P1NP1N2/1PP1QPPP/R4RK1 w - - 0 10",
+        )
+        .unwrap();
+        let mut searcher = Searcher::new(1);
+        let first = searcher.search(&mut board, depth(2));
+        let stats = searcher.table().stats();
+        assert!(stats.stores > 0);
+        assert!(stats.hits > 0);
+
+        let second = searcher.search(&mut board, depth(2));
+        assert_eq!(second.best_move, first.best_move);
+        assert_eq!(second.score, first.score);
+        assert!(second.nodes < first.nodes);
+        assert!(searcher.table().stats().hit_rate() > stats.hit_rate());
+    }
+
+    #[test]
+    fn looks_past_the_horizon_on_captures() {
+        // At depth 1 without a quiescence search the defended pawn looks free
+        let mut board = Board::from_fen("4k3/8/4p3/3p4/8/8/8/3QK3 w - - 0 1").unwrap();
+        let result = search(&mut board, depth(1));
+        assert_ne!(board.to_pgn(&result.best_move.unwrap()), "Qxd5");
+
+        let mut board = Board::from_fen("4k3/8/8/3p4/8/8/8/3QK3 w - - 0 1").unwrap();
+        let result = search(&mut board, depth(1));
+        assert_eq!(board.to_pgn(&result.best_move.unwrap()), "Qxd5");
+    }
+
+    #[test]
+    fn deterministic_searches_repeat_exactly() {
+        let mut board =
+            Board::from_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1")
+                .unwrap();
+        let mut searcher = Searcher::new(1);
+        searcher.set_deterministic(true);
+        let first = searcher.search(&mut board, depth(2));
+        let second = searcher.search(&mut board, depth(2));
+        assert_eq!(first, second);
+        let mut fresh = Searcher::new(1);
+        fresh.set_deterministic(true);
+        assert_eq!(fresh.search(&mut board, depth(2)), first);
+    }
+
+    #[test]
+    fn reports_progress_and_can_be_stopped() {
+        let mut board = Board::default();
+        let mut searcher = Searcher::new(1);
+        let mut depths: Vec<u32> = vec![];
+        let result = searcher.search_with_progress(&mut board, depth(3), |progress| {
+            depths.push(progress.result.depth);
+            assert!(!progress.result.pv.is_empty());
+        });
+        assert_eq!(depths, vec![1, 2, 3]);
+        assert_eq!(result.depth, 3);
+
+        searcher.stop_handle().store(true, Ordering::Relaxed);
+        let result = searcher.search(&mut board, SearchLimits::default());
+        assert_eq!(result.depth, 0);
+        assert!(result.best_move.is_some());
+    }
+
+    #[test]
+    fn reports_finished_games() {
+        let mut board = Board::from_fen("R5k1/5ppp/8/8/8/8/8/6K1 b - - 1 1").unwrap();
+        let result = search(&mut board, depth(2));
+        assert_eq!(result.best_move, None);
+        assert_eq!(result.score, -MATE_SCORE);
+    }
+}
@@ -0,0 +1,143 @@
+use crate::bitboard::Bitboard;
+use crate::board::{Board, Kind, Location, Move};
+
+/// Piece values for exchanges. The king is worth more than everything else put together, so
+/// it only ever captures last.
+pub fn exchange_value(kind: Kind) -> i32 {
+    return match kind {
+        Kind::Pawn => 100,
+        Kind::Knight => 320,
+        Kind::Bishop => 330,
+        Kind::Rook => 500,
+        Kind::Queen => 900,
+        Kind::King => 20_000,
+    };
+}
+
+const CAPTURE_ORDER: [Kind; 6] = [
+    Kind::Pawn,
+    Kind::Knight,
+    Kind::Bishop,
+    Kind::Rook,
+    Kind::Queen,
+    Kind::King,
+];
+
+/// The static exchange evaluation of `move`: the material the mover ends up winning, in
+/// centipawns, if both sides keep recapturing on the destination square with their least
+/// valuable piece for as long as it pays. Pins and checks are not taken into account.
+pub fn see(board: &Board, r#move: &Move) -> i32 {
+    let (from, to) = match r#move {
+        Move::Castle(_, _) => return 0,
+        Move::Simple(from, to) | Move::EnPassant(from, to) | Move::Promotion(from, to, _) => {
+            (*from, *to)
+        }
+    };
+    let mover = match board.get_piece(&from) {
+        Some(piece) => piece,
+        None => return 0,
+    };
+    let pieces = board.bitboards();
+    let mut occupied = pieces.occupied() ^ Bitboard::from_location(&from);
+    let mut gain = [0; 33];
+    gain[0] = match r#move {
+        Move::EnPassant(_, _) => {
+            occupied ^= Bitboard::from_location(&Location {
+                rank: from.rank,
+                file: to.file,
+            });
+            exchange_value(Kind::Pawn)
+        }
+        _ => board
+            .get_piece(&to)
+            .map_or(0, |piece| exchange_value(piece.kind())),
+    };
+    // The piece now standing on `to`, which is what the next capture wins
+    let mut on_square = mover.kind();
+    if let Move::Promotion(_, _, kind) = r#move {
+        gain[0] += exchange_value(*kind) - exchange_value(Kind::Pawn);
+        on_square = *kind;
+    }
+
+    let mut side = mover.color().opponent();
+    let mut depth = 0;
+    loop {
+        let attackers = pieces.attackers(&to, side, occupied);
+        let capturer = CAPTURE_ORDER.iter().find_map(|kind| {
+            return (attackers & pieces.pieces(side, *kind))
+                .first()
+                .map(|location| (*kind, location));
+        });
+        let (kind, location) = match capturer {
+            Some(capturer) => capturer,
+            None => break,
+        };
+        if kind == Kind::King && !pieces.attackers(&to, side.opponent(), occupied).is_empty() {
+            break;
+        }
+        depth += 1;
+        gain[depth] = exchange_value(on_square) - gain[depth - 1];
+        // Neither side can do better by continuing than by stopping here
+        if (-gain[depth - 1]).max(gain[depth]) < 0 {
+            break;
+        }
+        occupied ^= Bitboard::from_location(&location);
+        on_square = kind;
+        side = side.opponent();
+    }
+    while depth > 0 {
+        gain[depth - 1] = -(-gain[depth - 1]).max(gain[depth]);
+        depth -= 1;
+    }
+    return gain[0];
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn see_pgn(fen: &str, pgn: &str) -> i32 {
+        let board = Board::from_fen(fen).unwrap();
+        return see(&board, &board.parse_pgn_move(pgn).unwrap());
+    }
+
+    #[test]
+    fn counts_simple_captures() {
+        assert_eq!(see_pgn("4k3/8/8/3n4/8/8/8/3RK3 w - - 0 1", "Rxd5"), 320);
+        assert_eq!(see_pgn("4k3/8/4p3/3n4/2P5/8/8/4K3 w - - 0 1", "cxd5"), 220);
+        assert_eq!(see_pgn("4k3/8/4p3/3p4/8/8/8/3RK3 w - - 0 1", "Rxd5"), -400);
+        assert_eq!(see_pgn("4k3/8/8/8/8/8/3p4/3RK3 w - - 0 1", "Rxd2"), 100);
+        assert_eq!(see_pgn("4k3/8/8/8/8/8/8/3RK3 w - - 0 1", "Rc1"), 0);
+        assert_eq!(see_pgn("4k3/8/8/8/8/8/8/3RK3 w - - 0 1", "Rd8+"), -500);
+    }
+
+    #[test]
+    fn follows_x_rays() {
+        let fen = "3rk3/8/8/3p4/8/8/3R4/3RK3 w - - 0 1";
+        assert_eq!(see_pgn(fen, "Rxd5"), 100);
+        let fen = "3rk3/3r4/8/3p4/8/8/3R4/3RK3 w - - 0 1";
+        assert_eq!(see_pgn(fen, "Rxd5"), -400);
+    }
+
+    #[test]
+    fn stops_when_recapturing_loses() {
+        // Black does better not to recapture the pawn with the queen
+        let fen = "3qk3/8/8/3p4/4P3/8/8/3RK3 w - - 0 1";
+        assert_eq!(see_pgn(fen, "exd5"), 100);
+        // The king may only recapture on an undefended square
+        let fen = "8/8/8/3k4/4p3/8/8/4RK2 w - - 0 1";
+        assert_eq!(see_pgn(fen, "Rxe4"), -400);
+        let fen = "8/8/8/3k4/4p3/8/4R3/4RK2 w - - 0 1";
+        assert_eq!(see_pgn(fen, "Rxe4"), 100);
+    }
+
+    #[test]
+    fn handles_en_passant_and_promotion() {
+        let fen = "4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1";
+        assert_eq!(see_pgn(fen, "exd6"), 100);
+        let fen = "4k3/1P6/8/8/8/8/8/4K3 w - - 0 1";
+        assert_eq!(see_pgn(fen, "b8=Q+"), 800);
+        let fen = "1r2k3/P7/8/8/8/8/8/4K3 w - - 0 1";
+        assert_eq!(see_pgn(fen, "a8=Q"), -100);
+    }
+}
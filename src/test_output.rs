@@ -0,0 +1,29 @@
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+
+/// Output that a test can still read after handing it to an engine.
+#[derive(Clone, Default)]
+pub(crate) struct SharedOutput(Arc<Mutex<Vec<u8>>>);
+
+impl Write for SharedOutput {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        return self.0.lock().unwrap().write(buf);
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        return Ok(());
+    }
+}
+
+impl SharedOutput {
+    pub(crate) fn lines(&self) -> Vec<String> {
+        let text = String::from_utf8(self.0.lock().unwrap().clone()).unwrap();
+        return text.lines().map(String::from).collect();
+    }
+}
+
+/// An engine made by `new`, writing to output the test keeps a handle on.
+pub(crate) fn engine<E>(new: impl FnOnce(SharedOutput) -> E) -> (E, SharedOutput) {
+    let output = SharedOutput::default();
+    return (new(output.clone()), output);
+}
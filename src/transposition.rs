@@ -0,0 +1,266 @@
+use crate::board::Move;
+use crate::search::is_mate_score;
+
+/// Used when no size is asked for.
+pub const DEFAULT_MEGABYTES: usize = 16;
+
+/// How a stored score relates to the true score of the position.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Bound {
+    Exact,
+    /// The search failed high: the true score is at least this much.
+    Lower,
+    /// The search failed low: the true score is at most this much.
+    Upper,
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Entry {
+    pub key: u64,
+    pub depth: u32,
+    pub bound: Bound,
+    pub score: i32,
+    pub best_move: Option<Move>,
+    generation: u8,
+}
+
+/// Counters for judging how well the table is working.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct TableStats {
+    pub probes: u64,
+    pub hits: u64,
+    pub stores: u64,
+    /// Stores that evicted an entry for a different position.
+    pub replacements: u64,
+}
+
+impl TableStats {
+    /// The fraction of probes that found their position, from 0 to 1.
+    pub fn hit_rate(&self) -> f64 {
+        if self.probes == 0 {
+            return 0.0;
+        }
+        return self.hits as f64 / self.probes as f64;
+    }
+}
+
+/// Each position hashes to a bucket of two entries: one kept for the deepest search of the
+/// current generation, and one that always takes the newest result.
+const BUCKET_SIZE: usize = 2;
+
+/// Search results keyed by `Board::hash`, in a fixed amount of memory.
+pub struct TranspositionTable {
+    entries: Vec<Option<Entry>>,
+    generation: u8,
+    stats: TableStats,
+}
+
+impl TranspositionTable {
+    /// A table using at most `megabytes` of memory, though always at least one bucket.
+    pub fn new(megabytes: usize) -> Self {
+        let entry_size = std::mem::size_of::<Option<Entry>>();
+        let buckets = (megabytes * 1024 * 1024 / (entry_size * BUCKET_SIZE)).max(1);
+        return TranspositionTable {
+            entries: vec![None; buckets * BUCKET_SIZE],
+            generation: 0,
+            stats: TableStats::default(),
+        };
+    }
+
+    /// Forgets every entry and resets the statistics.
+    pub fn clear(&mut self) {
+        self.entries.iter_mut().for_each(|entry| *entry = None);
+        self.generation = 0;
+        self.stats = TableStats::default();
+    }
+
+    /// Marks the start of a new search, so entries left over from earlier ones are replaced first.
+    pub(crate) fn new_search(&mut self) {
+        self.generation = self.generation.wrapping_add(1);
+    }
+
+    pub fn stats(&self) -> TableStats {
+        return self.stats;
+    }
+
+    /// How full the table is in parts per thousand, judging by entries from the current search.
+    pub fn hashfull(&self) -> u32 {
+        let sample = self.entries.len().min(1000);
+        let used = self.entries[..sample]
+            .iter()
+            .filter(|entry| matches!(entry, Some(entry) if entry.generation == self.generation))
+            .count();
+        return (used * 1000 / sample) as u32;
+    }
+
+    fn bucket(&self, key: u64) -> usize {
+        return (key % (self.entries.len() / BUCKET_SIZE) as u64) as usize * BUCKET_SIZE;
+    }
+
+    /// The entry for the position with hash `key`, if there is one. Mate scores are returned
+    /// relative to the position `ply` plies from the root, rather than as stored.
+    pub(crate) fn probe(&mut self, key: u64, ply: i32) -> Option<Entry> {
+        self.stats.probes += 1;
+        let bucket = self.bucket(key);
+        for slot in bucket..bucket + BUCKET_SIZE {
+            if let Some(mut entry) = self.entries[slot] {
+                if entry.key == key {
+                    self.stats.hits += 1;
+                    entry.score = score_from_table(entry.score, ply);
+                    return Some(entry);
+                }
+            }
+        }
+        return None;
+    }
+
+    /// Records a search result for the position with hash `key`, found `ply` plies from the root.
+    pub(crate) fn store(
+        &mut self,
+        key: u64,
+        depth: u32,
+        bound: Bound,
+        score: i32,
+        best_move: Option<Move>,
+        ply: i32,
+    ) {
+        self.stats.stores += 1;
+        let bucket = self.bucket(key);
+        let mut entry = Entry {
+            key: key,
+            depth: depth,
+            bound: bound,
+            score: score_to_table(score, ply),
+            best_move: best_move,
+            generation: self.generation,
+        };
+
+        let existing_slot = (bucket..bucket + BUCKET_SIZE)
+            .find(|slot| matches!(self.entries[*slot], Some(existing) if existing.key == key));
+        let slot = match existing_slot {
+            Some(slot) => {
+                if entry.best_move.is_none() {
+                    entry.best_move = self.entries[slot].and_then(|existing| existing.best_move);
+                }
+                slot
+            }
+            None => {
+                let replace_preferred = match self.entries[bucket] {
+                    None => true,
+                    Some(existing) => {
+                        existing.generation != self.generation || depth >= existing.depth
+                    }
+                };
+                if self.entries[bucket].is_some() && self.entries[bucket + 1].is_some() {
+                    self.stats.replacements += 1;
+                }
+                if replace_preferred {
+                    // The entry losing its place may still be worth more than the newest one
+                    if self.entries[bucket].is_some() {
+                        self.entries[bucket + 1] = self.entries[bucket];
+                    }
+                    bucket
+                } else {
+                    bucket + 1
+                }
+            }
+        };
+        self.entries[slot] = Some(entry);
+    }
+}
+
+impl Default for TranspositionTable {
+    fn default() -> Self {
+        return TranspositionTable::new(DEFAULT_MEGABYTES);
+    }
+}
+
+/// Mate scores count plies from the root, but a stored entry may be reached at any ply, so they
+/// are stored counting from the position itself instead.
+fn score_to_table(score: i32, ply: i32) -> i32 {
+    if is_mate_score(score) {
+        if score > 0 {
+            return score + ply;
+        }
+        return score - ply;
+    }
+    return score;
+}
+
+fn score_from_table(score: i32, ply: i32) -> i32 {
+    if is_mate_score(score) {
+        if score > 0 {
+            return score - ply;
+        }
+        return score + ply;
+    }
+    return score;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::Location;
+    use crate::search::MATE_SCORE;
+
+    fn a_move() -> Move {
+        return Move::Simple(Location { rank: 1, file: 4 }, Location { rank: 3, file: 4 });
+    }
+
+    #[test]
+    fn stores_and_probes_entries() {
+        let mut table = TranspositionTable::new(1);
+        assert!(table.entries.len() > 1000);
+        assert_eq!(table.probe(42, 0), None);
+        table.store(42, 5, Bound::Lower, 35, Some(a_move()), 0);
+        let entry = table.probe(42, 3).unwrap();
+        assert_eq!(entry.depth, 5);
+        assert_eq!(entry.bound, Bound::Lower);
+        assert_eq!(entry.score, 35);
+        assert_eq!(entry.best_move, Some(a_move()));
+
+        // A result without a move keeps the move already known for the position
+        table.store(42, 6, Bound::Exact, 20, None, 0);
+        assert_eq!(table.probe(42, 0).unwrap().best_move, Some(a_move()));
+
+        let stats = table.stats();
+        assert_eq!(stats.probes, 3);
+        assert_eq!(stats.hits, 2);
+        assert_eq!(stats.stores, 2);
+        assert!((stats.hit_rate() - 2.0 / 3.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn adjusts_mate_scores_by_ply() {
+        let mut table = TranspositionTable::new(1);
+        // Mate found 5 plies from the root, at a node 2 plies from the root
+        table.store(7, 3, Bound::Exact, MATE_SCORE - 5, None, 2);
+        // The same position reached 4 plies from the root is mated 2 plies later
+        assert_eq!(table.probe(7, 4).unwrap().score, MATE_SCORE - 7);
+        table.store(8, 3, Bound::Exact, -MATE_SCORE + 5, None, 2);
+        assert_eq!(table.probe(8, 1).unwrap().score, -MATE_SCORE + 4);
+    }
+
+    #[test]
+    fn prefers_deeper_entries_from_the_same_search() {
+        let mut table = TranspositionTable::new(0);
+        assert_eq!(table.entries.len(), BUCKET_SIZE);
+        table.store(1, 8, Bound::Exact, 1, None, 0);
+        table.store(2, 2, Bound::Exact, 2, None, 0);
+        table.store(3, 3, Bound::Exact, 3, None, 0);
+        assert!(table.probe(1, 0).is_some());
+        assert!(table.probe(2, 0).is_none());
+        assert!(table.probe(3, 0).is_some());
+        assert_eq!(table.stats().replacements, 1);
+
+        table.new_search();
+        table.store(4, 1, Bound::Exact, 4, None, 0);
+        assert!(table.probe(4, 0).is_some());
+        assert!(table.probe(1, 0).is_some());
+        assert!(table.probe(3, 0).is_none());
+        assert_eq!(table.hashfull(), 500);
+
+        table.clear();
+        assert!(table.probe(1, 0).is_none());
+    }
+}
@@ -0,0 +1,393 @@
+use crate::board::{Board, Color};
+use crate::search::{is_mate_score, Progress, SearchLimits, Searcher, MATE_SCORE};
+use crate::transposition::{TranspositionTable, DEFAULT_MEGABYTES};
+use std::io::{BufRead, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+const MAX_HASH_MEGABYTES: usize = 4096;
+/// Kept back from the clock for the time it takes to send the move.
+const MOVE_OVERHEAD_MS: u64 = 50;
+/// Assumed moves left in the game when the GUI does not say.
+const DEFAULT_MOVES_TO_GO: u64 = 30;
+
+/// How long to think about one move with `clock` milliseconds left, gaining `increment` per move
+/// and with `moves_to_go` moves to make before the clock is next topped up, if it ever is.
+pub(crate) fn allocate_time(clock: u64, increment: u64, moves_to_go: Option<u64>) -> Duration {
+    let share = clock / moves_to_go.unwrap_or(DEFAULT_MOVES_TO_GO).max(1) + increment * 3 / 4;
+    let available = clock.saturating_sub(MOVE_OVERHEAD_MS).max(1);
+    return Duration::from_millis(share.min(available));
+}
+
+pub(crate) fn send<W: Write>(output: &Mutex<W>, line: &str) {
+    let mut output = output.lock().expect("Output lock poisoned");
+    writeln!(output, "{}", line).expect("Could not write to output");
+    output.flush().expect("Could not write to output");
+}
+
+/// A score as UCI writes it: `cp` in centipawns, or `mate` in moves, negative when being mated.
+pub(crate) fn score_text(score: i32) -> String {
+    if is_mate_score(score) {
+        let plies = MATE_SCORE - score.abs();
+        if score > 0 {
+            return format!("mate {}", (plies + 1) / 2);
+        }
+        return format!("mate -{}", plies / 2);
+    }
+    return format!("cp {}", score);
+}
+
+fn info_line(progress: &Progress) -> String {
+    let result = progress.result;
+    let millis = progress.elapsed.as_millis() as u64;
+    let pv: Vec<String> = result
+        .pv
+        .iter()
+        .map(|r#move| r#move.to_coordinates())
+        .collect();
+    return format!(
+        "info depth {} score {} nodes {} nps {} time {} hashfull {} pv {}",
+        result.depth,
+        score_text(result.score),
+        result.nodes,
+        result.nodes * 1000 / millis.max(1),
+        millis,
+        progress.hashfull,
+        pv.join(" ")
+    );
+}
+
+/// The Universal Chess Interface: reads commands from a GUI and answers on `output`. Searches
+/// run on their own thread so that `stop` and `isready` are answered while they think.
+pub struct Uci<W: Write + Send + 'static> {
+    output: Arc<Mutex<W>>,
+    board: Board,
+    /// Lent to the search thread while a search runs.
+    searcher: Option<Searcher>,
+    search: Option<JoinHandle<Searcher>>,
+    stop: Arc<AtomicBool>,
+}
+
+impl<W: Write + Send + 'static> Uci<W> {
+    pub fn new(output: W) -> Self {
+        let searcher = Searcher::new(DEFAULT_MEGABYTES);
+        return Uci {
+            output: Arc::new(Mutex::new(output)),
+            board: Board::default(),
+            stop: searcher.stop_handle(),
+            searcher: Some(searcher),
+            search: None,
+        };
+    }
+
+    /// Handles commands until `quit` or the end of `input`.
+    pub fn run<R: BufRead>(&mut self, input: R) {
+        for line in input.lines() {
+            let line = line.expect("Could not read from input");
+            if !self.handle(&line) {
+                return;
+            }
+        }
+        self.wait();
+    }
+
+    fn send(&self, line: &str) {
+        send(&self.output, line);
+    }
+
+    /// Waits for any search in progress to finish and send its move.
+    pub fn wait(&mut self) {
+        if let Some(search) = self.search.take() {
+            self.searcher = Some(search.join().expect("Search thread panicked"));
+        }
+    }
+
+    /// Stops any search in progress, which still sends its move, and waits for it to finish.
+    fn stop_search(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        self.wait();
+    }
+
+    fn searcher(&mut self) -> &mut Searcher {
+        self.stop_search();
+        return self.searcher.as_mut().expect("No searcher");
+    }
+
+    /// Handles one line of input, returning false once the GUI has asked to quit.
+    pub fn handle(&mut self, line: &str) -> bool {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let (command, args) = match words.split_first() {
+            Some((command, args)) => (*command, args),
+            None => return true,
+        };
+        match command {
+            "uci" => {
+                self.send(&format!(
+                    "id name {} {}",
+                    env!("CARGO_PKG_NAME"),
+                    env!("CARGO_PKG_VERSION")
+                ));
+                self.send(&format!("id author {}", env!("CARGO_PKG_AUTHORS")));
+                self.send(&format!(
+                    "option name Hash type spin default {} min 1 max {}",
+                    DEFAULT_MEGABYTES, MAX_HASH_MEGABYTES
+                ));
+                self.send("option name Clear Hash type button");
+                self.send("option name Deterministic type check default false");
+                self.send("uciok");
+            }
+            "isready" => self.send("readyok"),
+            "ucinewgame" => {
+                self.searcher().clear();
+                self.board = Board::default();
+            }
+            "position" => self.position(args),
+            "go" => self.go(args),
+            "stop" => self.stop_search(),
+            "setoption" => self.set_option(args),
+            "quit" => {
+                self.stop_search();
+                return false;
+            }
+            _ => self.send(&format!("info string Unknown command: {}", line.trim())),
+        }
+        return true;
+    }
+
+    /// `position startpos|fen <fen> [moves <move>...]`, with moves in coordinate notation.
+    fn position(&mut self, args: &[&str]) {
+        let moves_at = args
+            .iter()
+            .position(|arg| *arg == "moves")
+            .unwrap_or(args.len());
+        let mut board = match args.first() {
+            Some(&"startpos") => Board::default(),
+            Some(&"fen") => match Board::from_fen(&args[1..moves_at].join(" ")) {
+                Ok(board) => board,
+                Err(error) => {
+                    self.send(&format!("info string {}", error));
+                    return;
+                }
+            },
+            _ => {
+                self.send("info string Expected startpos or fen");
+                return;
+            }
+        };
+        for text in args.iter().skip(moves_at + 1) {
+            match board.parse_coordinate_move(text) {
+                Ok(r#move) => board.apply_move(r#move),
+                Err(error) => {
+                    self.send(&format!("info string {}", error));
+                    return;
+                }
+            }
+        }
+        self.board = board;
+    }
+
+    /// Works out the search limits from the arguments to `go`, and whether it is `infinite`.
+    fn limits(&self, args: &[&str]) -> (SearchLimits, bool) {
+        let mut limits = SearchLimits::default();
+        let mut infinite = false;
+        let mut clock: Option<u64> = None;
+        let mut increment: u64 = 0;
+        let mut moves_to_go: Option<u64> = None;
+        let (own_time, own_increment) = match self.board.to_move {
+            Color::White => ("wtime", "winc"),
+            Color::Black => ("btime", "binc"),
+        };
+
+        for i in 0..args.len() {
+            let value = args.get(i + 1).and_then(|value| value.parse::<u64>().ok());
+            match (args[i], value) {
+                ("infinite", _) => infinite = true,
+                ("depth", Some(depth)) => limits.depth = Some(depth as u32),
+                ("nodes", Some(nodes)) => limits.nodes = Some(nodes),
+                ("movetime", Some(millis)) => limits.time = Some(Duration::from_millis(millis)),
+                ("movestogo", Some(moves)) => moves_to_go = Some(moves.max(1)),
+                (name, Some(millis)) if name == own_time => clock = Some(millis),
+                (name, Some(millis)) if name == own_increment => increment = millis,
+                _ => {}
+            }
+        }
+
+        if let (None, Some(clock)) = (limits.time, clock) {
+            limits.time = Some(allocate_time(clock, increment, moves_to_go));
+        }
+        // Nothing to stop the search but `stop`, as with `go infinite`
+        if infinite || limits == SearchLimits::default() {
+            return (SearchLimits::default(), true);
+        }
+        return (limits, false);
+    }
+
+    /// Starts searching the current position, sending `info` after each iteration and then
+    /// `bestmove`. An infinite search only reports its move once told to `stop`.
+    fn go(&mut self, args: &[&str]) {
+        let (limits, infinite) = self.limits(args);
+        self.stop_search();
+        let mut searcher = self.searcher.take().expect("No searcher");
+        let mut board = self.board.clone();
+        let output = self.output.clone();
+        let stop = self.stop.clone();
+        stop.store(false, Ordering::Relaxed);
+        self.search = Some(thread::spawn(move || {
+            let result = searcher.search_with_progress(&mut board, limits, |progress| {
+                send(&output, &info_line(progress));
+            });
+            while infinite && !stop.load(Ordering::Relaxed) {
+                thread::sleep(Duration::from_millis(1));
+            }
+            let best_move = match result.best_move {
+                Some(r#move) => r#move.to_coordinates(),
+                None => String::from("0000"),
+            };
+            send(&output, &format!("bestmove {}", best_move));
+            return searcher;
+        }));
+    }
+
+    /// `setoption name <name> [value <value>]`.
+    fn set_option(&mut self, args: &[&str]) {
+        let value_at = args
+            .iter()
+            .position(|arg| *arg == "value")
+            .unwrap_or(args.len());
+        let name = args
+            .get(1..value_at)
+            .unwrap_or(&[])
+            .join(" ")
+            .to_lowercase();
+        let value = args.get(value_at + 1..).unwrap_or(&[]).join(" ");
+        match name.as_str() {
+            "hash" => match value.parse::<usize>() {
+                Ok(megabytes) if (1..=MAX_HASH_MEGABYTES).contains(&megabytes) => {
+                    *self.searcher().table_mut() = TranspositionTable::new(megabytes);
+                }
+                _ => self.send(&format!("info string Invalid Hash value: {}", value)),
+            },
+            "clear hash" => self.searcher().clear(),
+            "deterministic" => match value.as_str() {
+                "true" => self.searcher().set_deterministic(true),
+                "false" => self.searcher().set_deterministic(false),
+                _ => self.send(&format!(
+                    "info string Invalid Deterministic value: {}",
+                    value
+                )),
+            },
+            _ => self.send(&format!("info string Unknown option: {}", name)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_output::engine;
+
+    #[test]
+    fn identifies_itself() {
+        let (mut uci, output) = engine(Uci::new);
+        assert!(uci.handle("uci"));
+        assert!(uci.handle("isready"));
+        let lines = output.lines();
+        assert!(lines[0].starts_with("id name chess"));
+        assert!(lines
+            .iter()
+            .any(|line| line.starts_with("option name Hash")));
+        assert_eq!(lines[lines.len() - 2], "uciok");
+        assert_eq!(lines[lines.len() - 1], "readyok");
+        assert!(!uci.handle("quit"));
+    }
+
+    #[test]
+    fn searches_positions_with_moves() {
+        let (mut uci, output) = engine(Uci::new);
+        uci.handle("position startpos moves e2e4 e7e5 g1f3");
+        assert_eq!(
+            uci.board.to_fen(),
+            "rnbqkbnr/pppp1ppp/8/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R b KQkq - 1 2"
+        );
+        uci.handle("go depth 2");
+        uci.wait();
+        let lines = output.lines();
+        assert!(lines[0].starts_with("info depth 1 score cp "));
+        assert!(lines[1].starts_with("info depth 2 score cp "));
+        let best_move = lines[2].strip_prefix("bestmove ").unwrap();
+        assert!(uci.board.parse_coordinate_move(best_move).is_ok());
+    }
+
+    #[test]
+    fn reports_mates_in_moves() {
+        let (mut uci, output) = engine(Uci::new);
+        uci.handle("position fen 6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1");
+        uci.handle("go wtime 1000 btime 1000 depth 4");
+        uci.wait();
+        let lines = output.lines();
+        assert!(lines[0].contains(" score mate 1 "));
+        assert!(lines[0].ends_with(" pv a1a8"));
+        assert_eq!(lines[1], "bestmove a1a8");
+        assert_eq!(score_text(-MATE_SCORE + 2), "mate -1");
+    }
+
+    #[test]
+    fn infinite_searches_wait_for_stop() {
+        let (mut uci, output) = engine(Uci::new);
+        uci.handle("position fen 6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1");
+        uci.handle("go infinite");
+        thread::sleep(Duration::from_millis(50));
+        assert!(uci.handle("isready"));
+        assert!(!output
+            .lines()
+            .iter()
+            .any(|line| line.starts_with("bestmove")));
+        uci.handle("stop");
+        assert_eq!(output.lines().last().unwrap(), "bestmove a1a8");
+
+        // A plain `go` has no limits either
+        assert_eq!(uci.limits(&[]), (SearchLimits::default(), true));
+        assert!(!uci.limits(&["depth", "3"]).1);
+    }
+
+    #[test]
+    fn commands_during_an_infinite_search_stop_it() {
+        let (mut uci, output) = engine(Uci::new);
+        uci.handle("position fen 6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1");
+        uci.handle("go infinite");
+        uci.handle("setoption name Clear Hash");
+        assert!(output.lines().last().unwrap().starts_with("bestmove "));
+        uci.handle("go");
+        uci.handle("ucinewgame");
+        assert!(output.lines().last().unwrap().starts_with("bestmove "));
+        assert_eq!(
+            output
+                .lines()
+                .iter()
+                .filter(|line| line.starts_with("bestmove"))
+                .count(),
+            2
+        );
+        assert_eq!(uci.board.to_fen(), Board::default().to_fen());
+    }
+
+    #[test]
+    fn rejects_bad_input() {
+        let (mut uci, output) = engine(Uci::new);
+        uci.handle("position startpos moves e2e5");
+        uci.handle("setoption name Hash value lots");
+        uci.handle("setoption name Hash value 1");
+        uci.handle("flip");
+        assert_eq!(
+            output.lines(),
+            vec![
+                "info string No pieces can make the move: e2e5",
+                "info string Invalid Hash value: lots",
+                "info string Unknown command: flip",
+            ]
+        );
+        assert_eq!(uci.board.to_fen(), Board::default().to_fen());
+    }
+}
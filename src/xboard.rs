@@ -0,0 +1,434 @@
+use crate::board::{Board, Color, GameStatus, Move};
+use crate::search::{is_mate_score, Progress, SearchLimits, Searcher, MATE_SCORE};
+use crate::transposition::DEFAULT_MEGABYTES;
+use crate::uci::{allocate_time, send};
+use std::io::{BufRead, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+/// The time control used until the GUI sends `level`: 40 moves in 5 minutes.
+const DEFAULT_MOVES_PER_SESSION: u64 = 40;
+const DEFAULT_BASE_MS: u64 = 5 * 60 * 1000;
+
+/// The `result` line for a finished game, or None if it is still going.
+fn game_result(board: &Board) -> Option<String> {
+    return match board.status() {
+        GameStatus::Ongoing | GameStatus::Check => None,
+        GameStatus::Checkmate(Color::White) => Some(String::from("1-0 {White mates}")),
+        GameStatus::Checkmate(Color::Black) => Some(String::from("0-1 {Black mates}")),
+        GameStatus::Stalemate => Some(String::from("1/2-1/2 {Stalemate}")),
+        GameStatus::Draw(reason) => Some(format!("1/2-1/2 {{Draw by {}}}", reason)),
+    };
+}
+
+/// A score as CECP writes it: centipawns, or 100000 plus the moves to mate, negated when being
+/// mated.
+fn thinking_score(score: i32) -> i32 {
+    if is_mate_score(score) {
+        let plies = MATE_SCORE - score.abs();
+        if score > 0 {
+            return 100_000 + (plies + 1) / 2;
+        }
+        return -100_000 - plies / 2;
+    }
+    return score;
+}
+
+/// Thinking output as `post` asks for it: depth, score, time in centiseconds, nodes and the
+/// principal variation.
+fn thinking_line(progress: &Progress) -> String {
+    let result = progress.result;
+    let pv: Vec<String> = result
+        .pv
+        .iter()
+        .map(|r#move| r#move.to_coordinates())
+        .collect();
+    return format!(
+        "{} {} {} {} {}",
+        result.depth,
+        thinking_score(result.score),
+        progress.elapsed.as_millis() / 10,
+        result.nodes,
+        pv.join(" ")
+    );
+}
+
+/// Parses the base time of `level`, given either in minutes or as `minutes:seconds`.
+fn parse_base(text: &str) -> Option<u64> {
+    let mut parts = text.splitn(2, ':');
+    let minutes: u64 = parts.next()?.parse().ok()?;
+    let seconds: u64 = match parts.next() {
+        Some(seconds) => seconds.parse().ok()?,
+        None => 0,
+    };
+    return Some((minutes * 60 + seconds) * 1000);
+}
+
+fn parse_seconds(text: &str) -> Option<u64> {
+    let seconds: f64 = text.parse().ok()?;
+    if seconds < 0.0 {
+        return None;
+    }
+    return Some((seconds * 1000.0) as u64);
+}
+
+/// The Chess Engine Communication Protocol used by XBoard and WinBoard, version 2. Moves are
+/// in coordinate notation both ways, and the engine thinks on its own thread so that `?`,
+/// `force` and the like are handled while it does.
+pub struct XBoard<W: Write + Send + 'static> {
+    output: Arc<Mutex<W>>,
+    board: Board,
+    /// Lent to the search thread while the engine thinks.
+    searcher: Option<Searcher>,
+    /// Gives back the searcher and the move the engine played, if it played one.
+    search: Option<JoinHandle<(Searcher, Option<Move>)>>,
+    stop: Arc<AtomicBool>,
+    /// Set, with the output locked, when the result of a search is no longer wanted.
+    cancelled: Arc<AtomicBool>,
+    /// The color the engine plays, or None in force mode.
+    engine: Option<Color>,
+    post: bool,
+    moves_per_session: u64,
+    base: u64,
+    increment: u64,
+    /// The engine's own clock in milliseconds.
+    clock: u64,
+    /// Set by `st`, which overrides the clock.
+    move_time: Option<Duration>,
+    /// Set by `sd`.
+    depth: Option<u32>,
+}
+
+impl<W: Write + Send + 'static> XBoard<W> {
+    pub fn new(output: W) -> Self {
+        let searcher = Searcher::new(DEFAULT_MEGABYTES);
+        return XBoard {
+            output: Arc::new(Mutex::new(output)),
+            board: Board::default(),
+            stop: searcher.stop_handle(),
+            searcher: Some(searcher),
+            search: None,
+            cancelled: Arc::new(AtomicBool::new(false)),
+            engine: Some(Color::Black),
+            post: false,
+            moves_per_session: DEFAULT_MOVES_PER_SESSION,
+            base: DEFAULT_BASE_MS,
+            increment: 0,
+            clock: DEFAULT_BASE_MS,
+            move_time: None,
+            depth: None,
+        };
+    }
+
+    /// Handles commands until `quit` or the end of `input`.
+    pub fn run<R: BufRead>(&mut self, input: R) {
+        for line in input.lines() {
+            let line = line.expect("Could not read from input");
+            if !self.handle(&line) {
+                return;
+            }
+        }
+        self.wait();
+    }
+
+    fn send(&self, line: &str) {
+        send(&self.output, line);
+    }
+
+    /// Waits for the engine to finish thinking, and plays the move it sent.
+    pub fn wait(&mut self) {
+        if let Some(search) = self.search.take() {
+            let (searcher, played) = search.join().expect("Search thread panicked");
+            self.searcher = Some(searcher);
+            if let Some(r#move) = played {
+                self.board.apply_move(r#move);
+            }
+        }
+    }
+
+    /// Stops the engine thinking without it sending a move, unless it already has.
+    fn cancel(&mut self) {
+        {
+            let _output = self.output.lock().expect("Output lock poisoned");
+            self.cancelled.store(true, Ordering::Relaxed);
+        }
+        self.stop.store(true, Ordering::Relaxed);
+        self.wait();
+    }
+
+    /// Handles one line of input, returning false once the GUI has asked to quit.
+    pub fn handle(&mut self, line: &str) -> bool {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let (command, args) = match words.split_first() {
+            Some((command, args)) => (*command, args),
+            None => return true,
+        };
+        match command {
+            "xboard" | "accepted" | "rejected" | "random" | "hard" | "easy" | "computer"
+            | "otim" | "name" | "rating" => {}
+            "protover" => self.send(&format!(
+                "feature myname=\"{} {}\" usermove=1 setboard=1 ping=1 playother=1 colors=0 \
+                 sigint=0 sigterm=0 done=1",
+                env!("CARGO_PKG_NAME"),
+                env!("CARGO_PKG_VERSION")
+            )),
+            "ping" => self.send(&format!("pong {}", args.join(" "))),
+            "new" => {
+                self.cancel();
+                self.board = Board::default();
+                self.engine = Some(Color::Black);
+                self.clock = self.base;
+                self.depth = None;
+                self.searcher.as_mut().expect("No searcher").clear();
+            }
+            "force" => {
+                self.cancel();
+                self.engine = None;
+            }
+            "go" => {
+                self.wait();
+                self.engine = Some(self.board.to_move);
+                self.think();
+            }
+            "playother" => {
+                self.cancel();
+                self.engine = Some(self.board.to_move.opponent());
+            }
+            "usermove" => self.user_move(args.first().copied().unwrap_or("")),
+            "?" => self.stop.store(true, Ordering::Relaxed),
+            "undo" => {
+                self.cancel();
+                self.board.undo_move();
+            }
+            "remove" => {
+                self.cancel();
+                self.board.undo_move();
+                self.board.undo_move();
+            }
+            "setboard" => {
+                self.cancel();
+                match Board::from_fen(&args.join(" ")) {
+                    Ok(board) => self.board = board,
+                    Err(_) => self.send("tellusererror Illegal position"),
+                }
+            }
+            "level" => self.level(args),
+            "st" => match args.first().and_then(|text| parse_seconds(text)) {
+                Some(millis) => self.move_time = Some(Duration::from_millis(millis)),
+                None => self.send(&format!("Error (bad time): {}", line.trim())),
+            },
+            "sd" => match args.first().and_then(|text| text.parse::<u32>().ok()) {
+                Some(depth) => self.depth = Some(depth),
+                None => self.send(&format!("Error (bad depth): {}", line.trim())),
+            },
+            "time" => match args.first().and_then(|text| text.parse::<u64>().ok()) {
+                Some(centiseconds) => self.clock = centiseconds * 10,
+                None => self.send(&format!("Error (bad time): {}", line.trim())),
+            },
+            "result" => {
+                self.cancel();
+                self.engine = None;
+            }
+            "post" => self.post = true,
+            "nopost" => self.post = false,
+            "quit" => {
+                self.cancel();
+                return false;
+            }
+            _ => self.send(&format!("Error (unknown command): {}", command)),
+        }
+        return true;
+    }
+
+    /// `level <moves> <base> <increment>`: `moves` per session, or 0 for the whole game, with
+    /// `base` minutes and `increment` seconds added after each move.
+    fn level(&mut self, args: &[&str]) {
+        let moves = args.first().and_then(|text| text.parse::<u64>().ok());
+        let base = args.get(1).and_then(|text| parse_base(text));
+        let increment = args.get(2).and_then(|text| parse_seconds(text));
+        match (moves, base, increment) {
+            (Some(moves), Some(base), Some(increment)) => {
+                self.moves_per_session = moves;
+                self.base = base;
+                self.increment = increment;
+                self.clock = base;
+                self.move_time = None;
+            }
+            _ => self.send(&format!(
+                "Error (bad time control): level {}",
+                args.join(" ")
+            )),
+        }
+    }
+
+    fn user_move(&mut self, text: &str) {
+        self.wait();
+        match self.board.parse_coordinate_move(text) {
+            Ok(r#move) => self.board.apply_move(r#move),
+            Err(_) => {
+                self.send(&format!("Illegal move: {}", text));
+                return;
+            }
+        }
+        if let Some(result) = game_result(&self.board) {
+            self.send(&format!("result {}", result));
+            return;
+        }
+        if self.engine == Some(self.board.to_move) {
+            self.think();
+        }
+    }
+
+    fn limits(&self) -> SearchLimits {
+        let time = match self.move_time {
+            Some(time) => time,
+            None => {
+                let moves_to_go = if self.moves_per_session > 0 {
+                    let played = u64::from(self.board.fullmove_number.max(1) - 1);
+                    Some(self.moves_per_session - played % self.moves_per_session)
+                } else {
+                    None
+                };
+                allocate_time(self.clock, self.increment, moves_to_go)
+            }
+        };
+        return SearchLimits {
+            depth: self.depth,
+            nodes: None,
+            time: Some(time),
+        };
+    }
+
+    /// Starts the engine thinking about its move, which it sends and then plays.
+    fn think(&mut self) {
+        if game_result(&self.board).is_some() {
+            return;
+        }
+        let limits = self.limits();
+        let mut searcher = self.searcher.take().expect("No searcher");
+        let mut board = self.board.clone();
+        let output = self.output.clone();
+        let cancelled = self.cancelled.clone();
+        let post = self.post;
+        self.stop.store(false, Ordering::Relaxed);
+        cancelled.store(false, Ordering::Relaxed);
+        self.search = Some(thread::spawn(move || {
+            let result = searcher.search_with_progress(&mut board, limits, |progress| {
+                if post {
+                    send(&output, &thinking_line(progress));
+                }
+            });
+            let r#move = match result.best_move {
+                Some(r#move) => r#move,
+                None => return (searcher, None),
+            };
+            {
+                let _output = output.lock().expect("Output lock poisoned");
+                if cancelled.load(Ordering::Relaxed) {
+                    return (searcher, None);
+                }
+            }
+            send(&output, &format!("move {}", r#move.to_coordinates()));
+            board.apply_move(r#move);
+            if let Some(result) = game_result(&board) {
+                send(&output, &format!("result {}", result));
+            }
+            return (searcher, Some(r#move));
+        }));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_output::engine;
+
+    #[test]
+    fn negotiates_features() {
+        let (mut xboard, output) = engine(XBoard::new);
+        xboard.handle("xboard");
+        xboard.handle("protover 2");
+        xboard.handle("ping 7");
+        let lines = output.lines();
+        assert!(lines[0].starts_with("feature myname=\"chess "));
+        assert!(lines[0].contains(" usermove=1 setboard=1 "));
+        assert!(lines[0].ends_with(" done=1"));
+        assert_eq!(lines[1], "pong 7");
+        assert!(!xboard.handle("quit"));
+    }
+
+    #[test]
+    fn replies_to_user_moves() {
+        let (mut xboard, output) = engine(XBoard::new);
+        xboard.handle("new");
+        xboard.handle("sd 2");
+        xboard.handle("usermove e2e4");
+        xboard.wait();
+        let lines = output.lines();
+        assert_eq!(lines.len(), 1);
+        let reply = lines[0].strip_prefix("move ").unwrap();
+        xboard.board.undo_move();
+        assert_eq!(xboard.board.to_move, Color::Black);
+        assert!(xboard.board.parse_coordinate_move(reply).is_ok());
+    }
+
+    #[test]
+    fn force_mode_only_records_moves() {
+        let (mut xboard, output) = engine(XBoard::new);
+        xboard.handle("force");
+        xboard.handle("usermove e2e4");
+        xboard.handle("usermove e7e5");
+        xboard.handle("usermove e1e2");
+        xboard.handle("undo");
+        assert_eq!(
+            xboard.board.to_fen(),
+            "rnbqkbnr/pppp1ppp/8/4p3/4P3/8/PPPP1PPP/RNBQKBNR w KQkq e6 0 2"
+        );
+        xboard.handle("usermove e2e5");
+        xboard.handle("castle");
+        assert_eq!(
+            output.lines(),
+            vec!["Illegal move: e2e5", "Error (unknown command): castle"]
+        );
+    }
+
+    #[test]
+    fn plays_and_reports_results() {
+        let (mut xboard, output) = engine(XBoard::new);
+        xboard.handle("setboard 6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1");
+        xboard.handle("st 10");
+        xboard.handle("sd 3");
+        xboard.handle("post");
+        xboard.handle("go");
+        xboard.wait();
+        let lines = output.lines();
+        assert!(lines[0].starts_with("1 100001 "));
+        assert!(lines[0].ends_with(" a1a8"));
+        assert_eq!(lines[lines.len() - 2], "move a1a8");
+        assert_eq!(lines[lines.len() - 1], "result 1-0 {White mates}");
+        assert_eq!(xboard.board.status(), GameStatus::Checkmate(Color::White));
+        assert_eq!(thinking_score(-MATE_SCORE + 4), -100_002);
+        assert_eq!(thinking_score(-35), -35);
+    }
+
+    #[test]
+    fn sets_time_controls() {
+        let (mut xboard, output) = engine(XBoard::new);
+        xboard.handle("level 40 0:30 0");
+        assert_eq!(xboard.limits().time, Some(Duration::from_millis(750)));
+        xboard.handle("level 0 2 1.5");
+        xboard.handle("time 6000");
+        assert_eq!(xboard.limits().time, Some(Duration::from_millis(3125)));
+        xboard.handle("st 2");
+        xboard.handle("sd 6");
+        assert_eq!(xboard.limits().time, Some(Duration::from_secs(2)));
+        assert_eq!(xboard.limits().depth, Some(6));
+        xboard.handle("level 40 soon 0");
+        assert_eq!(
+            output.lines(),
+            vec!["Error (bad time control): level 40 soon 0"]
+        );
+    }
+}
@@ -0,0 +1,67 @@
+use crate::bitboard::{self, Bitboards};
+use crate::board::{CastlingRights, Color, Kind, Location};
+
+const PIECE_KEYS: usize = 2 * 6 * 64;
+const SIDE_KEY: usize = PIECE_KEYS;
+const CASTLING_KEYS: usize = SIDE_KEY + 1;
+const EN_PASSANT_KEYS: usize = CASTLING_KEYS + 4;
+const KEY_COUNT: usize = EN_PASSANT_KEYS + 8;
+
+/// Pseudo-random keys from splitmix64 with a fixed seed, so hashes are stable across runs and
+/// builds.
+const fn zobrist_keys() -> [u64; KEY_COUNT] {
+    let mut result = [0; KEY_COUNT];
+    let mut state: u64 = 0x5eed_c4e5_5eed_c4e5;
+    let mut i = 0;
+    while i < KEY_COUNT {
+        state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut key = state;
+        key = (key ^ (key >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        key = (key ^ (key >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        result[i] = key ^ (key >> 31);
+        i += 1;
+    }
+    return result;
+}
+
+const KEYS: [u64; KEY_COUNT] = zobrist_keys();
+
+pub fn piece(color: Color, kind: Kind, location: &Location) -> u64 {
+    return KEYS[(color.index() * 6 + kind.index()) * 64 + bitboard::square_index(location)];
+}
+
+/// Included in the hash when Black is to move.
+pub fn black_to_move() -> u64 {
+    return KEYS[SIDE_KEY];
+}
+
+pub fn castling(rights: &CastlingRights) -> u64 {
+    let mut result = 0;
+    for (i, allowed) in [
+        rights.white_kingside,
+        rights.white_queenside,
+        rights.black_kingside,
+        rights.black_queenside,
+    ]
+    .iter()
+    .enumerate()
+    {
+        if *allowed {
+            result ^= KEYS[CASTLING_KEYS + i];
+        }
+    }
+    return result;
+}
+
+/// The key for an en-passant square. It only counts when a pawn of `to_move` could actually
+/// capture there, so positions that differ in nothing else hash the same.
+pub fn en_passant(en_passant: Option<Location>, to_move: Color, pieces: &Bitboards) -> u64 {
+    if let Some(location) = en_passant {
+        let capturers = bitboard::pawn_attacks(to_move.opponent(), &location)
+            & pieces.pieces(to_move, Kind::Pawn);
+        if !capturers.is_empty() {
+            return KEYS[EN_PASSANT_KEYS + location.file as usize];
+        }
+    }
+    return 0;
+}
@@ -0,0 +1,77 @@
+use chess::Board;
+
+// Reference node counts from https://www.chessprogramming.org/Perft_Results
+
+fn assert_perft(fen: &str, expected: &[u64]) {
+    let board = Board::from_fen(fen).unwrap();
+    for (i, nodes) in expected.iter().enumerate() {
+        let depth = i as u32 + 1;
+        assert_eq!(board.perft(depth), *nodes, "perft({}) of {}", depth, fen);
+    }
+}
+
+#[test]
+fn start_position() {
+    assert_perft(
+        "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+        &[20, 400, 8902],
+    );
+}
+
+#[test]
+fn kiwipete() {
+    assert_perft(
+        "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+        &[48, 2039, 97862],
+    );
+}
+
+#[test]
+fn position_3() {
+    assert_perft(
+        "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+        &[14, 191, 2812],
+    );
+}
+
+#[test]
+fn position_4() {
+    assert_perft(
+        "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+        &[6, 264, 9467],
+    );
+}
+
+#[test]
+fn position_5() {
+    assert_perft(
+        "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+        &[44, 1486, 62379],
+    );
+}
+
+#[test]
+fn position_6() {
+    assert_perft(
+        "r4rk1/1pp1qppp/p1np1n2/2b1p1B1/2B1P1b1/P1NP1N2/1PP1QPPP/R4RK1 w - - 0 10",
+        &[46, 2079],
+    );
+}
+
+#[test]
+fn divide_sums_to_perft() {
+    let board = Board::default();
+    let divide = board.divide(3);
+    assert_eq!(divide.len(), 20);
+    assert_eq!(divide.iter().map(|(_, nodes)| nodes).sum::<u64>(), 8902);
+}
+
+#[test]
+fn divide_matches_perft_at_shallow_depths() {
+    let board = Board::default();
+    assert_eq!(board.perft(0), 1);
+    assert!(board.divide(0).is_empty());
+    let divide = board.divide(1);
+    assert_eq!(divide.len(), 20);
+    assert!(divide.iter().all(|(_, nodes)| *nodes == 1));
+}